@@ -1 +1,8 @@
+pub mod model;
+pub mod normalize;
+pub mod pool;
 pub mod processor;
+pub mod session;
+pub mod temporal;
+pub mod tiling;
+pub mod upscaler;
@@ -0,0 +1,303 @@
+// src/ai/model.rs
+
+use std::fmt;
+use onnxruntime::{session::Session, tensor::OrtOwnedTensor, TensorElementDataType};
+use ndarray::{Array4, Array5, ArrayD, IxDyn};
+use anyhow::{Result, anyhow};
+use crate::ai::temporal::DEFAULT_WINDOW;
+
+/// Spatial size fed to models with a dynamic height/width to discover their scale factor.
+const PROBE_SIZE: u32 = 64;
+
+/// Memory layout of the model's image tensors. Temporal models add a time axis
+/// after the batch axis (NTCHW / NTHWC).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TensorLayout {
+    Nchw,
+    Nhwc,
+}
+
+impl TensorLayout {
+    /// Array shape for `n` images of `c` channels at `h`x`w`.
+    pub fn shape(&self, n: usize, c: usize, h: usize, w: usize) -> (usize, usize, usize, usize) {
+        match self {
+            TensorLayout::Nchw => (n, c, h, w),
+            TensorLayout::Nhwc => (n, h, w, c),
+        }
+    }
+
+    /// Tells the layout of a 4-D model input from which of axes 1 and 3 holds 1 or 3
+    /// channels. Shapes where both or neither could are rejected rather than guessed.
+    pub fn detect(dims: &[Option<u32>]) -> Result<Self> {
+        let is_channels = |d: Option<u32>| matches!(d, Some(1) | Some(3));
+        match (is_channels(dims[1]), is_channels(dims[3])) {
+            (true, false) => Ok(TensorLayout::Nchw),
+            (false, true) => Ok(TensorLayout::Nhwc),
+            (true, true) => Err(anyhow!("Cannot tell NCHW from NHWC in model input shape {:?}, both axes 1 and 3 could hold the channels", dims)),
+            (false, false) => Err(anyhow!("Cannot find a 1- or 3-channel axis in model input shape {:?}", dims)),
+        }
+    }
+
+    /// Splits a 4-D tensor shape, or a 5-D one with a time axis, into (channels, height, width).
+    pub fn chw(&self, shape: &[usize]) -> Result<(usize, usize, usize)> {
+        let &[.., a, b, c] = shape else { return Err(anyhow!("Expected an image tensor, got shape {:?}", shape)) };
+        if shape.len() != 4 && shape.len() != 5 {
+            return Err(anyhow!("Expected a 4-D or 5-D image tensor, got shape {:?}", shape));
+        }
+        Ok(match self {
+            TensorLayout::Nchw => (a, b, c),
+            TensorLayout::Nhwc => (c, a, b),
+        })
+    }
+
+    /// Packs planar `c`x`h`x`w` images into a tensor of `batch` images in this layout.
+    /// Slots past the end of `images` are zero, for models with a fixed batch size.
+    pub fn pack(&self, images: &[Vec<f32>], batch: usize, c: usize, h: usize, w: usize) -> Result<Array4<f32>> {
+        let data = self.pack_data(images, batch, c, h, w)?;
+        Ok(Array4::from_shape_vec(self.shape(batch, c, h, w), data)?)
+    }
+
+    /// Packs windows of `frames` planar images each into a 5-D tensor of `batch` windows,
+    /// with the time axis after the batch axis.
+    pub fn pack_windows<I: AsRef<[f32]>>(&self, windows: &[Vec<I>], batch: usize, frames: usize, c: usize, h: usize, w: usize) -> Result<Array5<f32>> {
+        if windows.len() > batch {
+            return Err(anyhow!("Cannot pack {} windows into a batch of {}", windows.len(), batch));
+        }
+        if let Some(window) = windows.iter().find(|window| window.len() != frames) {
+            return Err(anyhow!("Expected windows of {} frames, got {}", frames, window.len()));
+        }
+        let images: Vec<&[f32]> = windows.iter().flatten().map(|image| image.as_ref()).collect();
+        let data = self.pack_data(&images, batch * frames, c, h, w)?;
+        let (n, a, b, d) = self.shape(batch, c, h, w);
+        Ok(Array5::from_shape_vec((n, frames, a, b, d), data)?)
+    }
+
+    fn pack_data<I: AsRef<[f32]>>(&self, images: &[I], batch: usize, c: usize, h: usize, w: usize) -> Result<Vec<f32>> {
+        let image_len = c * h * w;
+        if images.len() > batch {
+            return Err(anyhow!("Cannot pack {} images into a batch of {}", images.len(), batch));
+        }
+        let mut data = vec![0.0; batch * image_len];
+        for (planes, dst) in images.iter().zip(data.chunks_exact_mut(image_len)) {
+            let planes = planes.as_ref();
+            match self {
+                TensorLayout::Nchw => dst.copy_from_slice(&planes[..image_len]),
+                TensorLayout::Nhwc => {
+                    let plane_len = h * w;
+                    for i in 0..plane_len {
+                        for ch in 0..c {
+                            dst[i * c + ch] = planes[ch * plane_len + i];
+                        }
+                    }
+                }
+            }
+        }
+        Ok(data)
+    }
+
+    /// Unpacks image `index` of a tensor in this layout back into planar `c`x`h`x`w` data.
+    /// In a 5-D tensor images are counted across the batch and time axes together.
+    pub fn unpack(&self, tensor: &[f32], index: usize, c: usize, h: usize, w: usize) -> Vec<f32> {
+        let plane_len = h * w;
+        let image = &tensor[index * c * plane_len..(index + 1) * c * plane_len];
+        match self {
+            TensorLayout::Nchw => image.to_vec(),
+            TensorLayout::Nhwc => {
+                let mut planes = vec![0.0; c * plane_len];
+                for i in 0..plane_len {
+                    for ch in 0..c {
+                        planes[ch * plane_len + i] = image[i * c + ch];
+                    }
+                }
+                planes
+            }
+        }
+    }
+}
+
+/// What the ONNX model expects and produces, read from the session when it is loaded.
+#[derive(Debug, Clone)]
+pub struct ModelInfo {
+    pub input_name: String,
+    pub layout: TensorLayout,
+    pub batch: Option<u32>,              // None = dynamic, fixed batches are zero-padded
+    pub channels: u32,
+    pub input_size: Option<(u32, u32)>,  // (width, height), None = dynamic
+    pub output_channels: u32,
+    pub scale: u32,                      // native upscale factor, e.g. 2 or 4
+    pub frames: Option<u32>,             // frames per window of a temporal model, None = one frame
+    pub output_window: bool,             // temporal model returns the whole window, not just its center
+}
+
+impl ModelInfo {
+    /// Inspects the session's input/output metadata. Models with dynamic dimensions are
+    /// run once on a small blank tensor to discover the output channel count and scale.
+    pub fn from_session(session: &mut Session) -> Result<Self> {
+        if session.inputs.len() != 1 {
+            return Err(anyhow!("Expected a model with a single image input, found {}", session.inputs.len()));
+        }
+        let output = session.outputs.first().ok_or_else(|| anyhow!("Model has no outputs"))?;
+        let input = &session.inputs[0];
+
+        if !matches!(input.input_type, TensorElementDataType::Float) {
+            return Err(anyhow!("Model input must be float32, got {:?}", input.input_type));
+        }
+        if !matches!(output.output_type, TensorElementDataType::Float) {
+            return Err(anyhow!("Model output must be float32, got {:?}", output.output_type));
+        }
+
+        // Temporal models take a window of frames on an extra axis after the batch
+        let (dims, frames) = split_time_axis(&input.dimensions);
+        let frames = frames.map(|t| t.unwrap_or(DEFAULT_WINDOW));
+        if dims.len() != 4 {
+            return Err(anyhow!("Expected a 4-D image input or a 5-D frame window, model input shape is {:?}", input.dimensions));
+        }
+        let layout = TensorLayout::detect(&dims)?;
+        let (c, h, w) = match layout {
+            TensorLayout::Nchw => (dims[1], dims[2], dims[3]),
+            TensorLayout::Nhwc => (dims[3], dims[1], dims[2]),
+        };
+        let channels = c.unwrap_or(1);
+        let input_size = match (w, h) {
+            (Some(w), Some(h)) => Some((w, h)),
+            (None, None) => None,
+            _ => return Err(anyhow!("Model input must be fixed or dynamic on both spatial axes, got {:?}", input.dimensions)),
+        };
+
+        let batch = dims[0].filter(|&b| b > 0);
+
+        // A temporal model returns either the center frame or the whole window
+        let (out_dims, out_frames) = split_time_axis(&output.dimensions);
+        if out_dims.len() != 4 || (frames.is_none() && out_frames.is_some()) {
+            return Err(anyhow!("Expected a 4-D image output, model output shape is {:?}", output.dimensions));
+        }
+        let output_window = out_frames.is_some();
+        let (oc, oh, ow) = match layout {
+            TensorLayout::Nchw => (out_dims[1], out_dims[2], out_dims[3]),
+            TensorLayout::Nhwc => (out_dims[3], out_dims[1], out_dims[2]),
+        };
+
+        let (output_channels, scale) = match (input_size, oc, oh, ow) {
+            (Some((iw, ih)), Some(oc), Some(oh), Some(ow)) => (oc, scale_factor(iw, ih, ow, oh)?),
+            _ => {
+                let (pw, ph) = input_size.unwrap_or((PROBE_SIZE, PROBE_SIZE));
+                let (n, a, b, d) = layout.shape(batch.unwrap_or(1) as usize, channels as usize, ph as usize, pw as usize);
+                let shape = match frames {
+                    Some(t) => vec![n, t as usize, a, b, d],
+                    None => vec![n, a, b, d],
+                };
+                let outputs: Vec<OrtOwnedTensor<f32, IxDyn>> = session.run(vec![ArrayD::<f32>::zeros(shape)])?;
+                let (oc, oh, ow) = layout.chw(outputs[0].shape())?;
+                (oc as u32, scale_factor(pw, ph, ow as u32, oh as u32)?)
+            }
+        };
+
+        if output_channels != 1 && output_channels != 3 {
+            return Err(anyhow!("Model must output 1 or 3 channels, got {}", output_channels));
+        }
+
+        Ok(Self {
+            input_name: session.inputs[0].name.clone(),
+            layout,
+            batch,
+            channels,
+            input_size,
+            output_channels,
+            scale,
+            frames,
+            output_window,
+        })
+    }
+
+    /// Size of the model output for an input of `w`x`h`.
+    pub fn output_size(&self, w: u32, h: u32) -> (u32, u32) {
+        (w * self.scale, h * self.scale)
+    }
+}
+
+impl fmt::Display for ModelInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let size = match self.input_size {
+            Some((w, h)) => format!("{}x{}", w, h),
+            None => "dynamic".to_string(),
+        };
+        let batch = match self.batch {
+            Some(b) => b.to_string(),
+            None => "dynamic".to_string(),
+        };
+        write!(f, "{} ({:?}, batch {}, {}ch {} -> {}ch, x{}",
+            self.input_name, self.layout, batch, self.channels, size, self.output_channels, self.scale)?;
+        match self.frames {
+            Some(t) => write!(f, ", {}-frame window)", t),
+            None => write!(f, ")"),
+        }
+    }
+}
+
+// Splits off the time axis of a 5-D shape, returning the remaining 4-D shape and the
+// axis length (None if dynamic); 4-D shapes have no time axis
+fn split_time_axis(dims: &[Option<u32>]) -> (Vec<Option<u32>>, Option<Option<u32>>) {
+    if dims.len() == 5 {
+        let mut rest = dims.to_vec();
+        let frames = rest.remove(1);
+        (rest, Some(frames))
+    } else {
+        (dims.to_vec(), None)
+    }
+}
+
+fn scale_factor(iw: u32, ih: u32, ow: u32, oh: u32) -> Result<u32> {
+    if iw == 0 || ih == 0 || ow == 0 || oh == 0 {
+        return Err(anyhow!("Model maps {}x{} to {}x{}, sizes must not be zero", iw, ih, ow, oh));
+    }
+    if ow < iw || ow % iw != 0 || oh % ih != 0 || ow / iw != oh / ih {
+        return Err(anyhow!("Model output {}x{} is not an integer upscale of its input {}x{}", ow, oh, iw, ih));
+    }
+    Ok(ow / iw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_factor_accepts_integer_upscales_only() {
+        assert_eq!(scale_factor(64, 64, 256, 256).unwrap(), 4);
+        assert_eq!(scale_factor(64, 32, 128, 64).unwrap(), 2);
+        assert_eq!(scale_factor(64, 64, 64, 64).unwrap(), 1);
+        assert!(scale_factor(64, 64, 32, 32).is_err());
+        assert!(scale_factor(64, 64, 96, 96).is_err());
+        assert!(scale_factor(64, 64, 128, 256).is_err());
+        assert!(scale_factor(0, 0, 0, 0).is_err());
+        assert!(scale_factor(0, 64, 128, 128).is_err());
+    }
+
+    #[test]
+    fn layout_follows_the_channel_axis() {
+        assert_eq!(TensorLayout::detect(&[Some(1), Some(1), None, None]).unwrap(), TensorLayout::Nchw);
+        assert_eq!(TensorLayout::detect(&[None, Some(3), Some(64), Some(64)]).unwrap(), TensorLayout::Nchw);
+        assert_eq!(TensorLayout::detect(&[Some(1), None, None, Some(3)]).unwrap(), TensorLayout::Nhwc);
+        assert_eq!(TensorLayout::detect(&[Some(1), Some(64), Some(64), Some(1)]).unwrap(), TensorLayout::Nhwc);
+    }
+
+    #[test]
+    fn layout_rejects_ambiguous_and_channelless_shapes() {
+        // NHWC 3x3 RGB or NCHW of width 3, no way to tell
+        assert!(TensorLayout::detect(&[Some(1), Some(3), Some(3), Some(3)]).is_err());
+        assert!(TensorLayout::detect(&[Some(1), Some(1), Some(64), Some(3)]).is_err());
+        assert!(TensorLayout::detect(&[Some(1), None, None, None]).is_err());
+        assert!(TensorLayout::detect(&[Some(1), Some(64), Some(64), Some(4)]).is_err());
+    }
+
+    #[test]
+    fn layout_splits_shapes() {
+        assert_eq!(TensorLayout::Nchw.shape(2, 3, 4, 5), (2, 3, 4, 5));
+        assert_eq!(TensorLayout::Nhwc.shape(2, 3, 4, 5), (2, 4, 5, 3));
+        assert_eq!(TensorLayout::Nchw.chw(&[1, 3, 8, 6]).unwrap(), (3, 8, 6));
+        assert_eq!(TensorLayout::Nhwc.chw(&[1, 8, 6, 3]).unwrap(), (3, 8, 6));
+        // Temporal models add a time axis after the batch axis
+        assert_eq!(TensorLayout::Nchw.chw(&[1, 5, 3, 8, 6]).unwrap(), (3, 8, 6));
+        assert!(TensorLayout::Nchw.chw(&[3, 8, 6]).is_err());
+        assert!(TensorLayout::Nchw.chw(&[1, 1, 5, 3, 8, 6]).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+// src/ai/normalize.rs
+
+/// How samples are scaled into the model and how its output is mapped back to 0-255.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NormalizationMode {
+    /// Model works in [0, 1]; output is clamped to that range.
+    #[default]
+    ClampUnit,
+    /// Model works in [0, 255]; output is clamped to that range.
+    Clamp255,
+    /// Input in [0, 1]; output is stretched to its own per-frame min/max (auto-contrast).
+    MinMax,
+    /// Like `MinMax`, but the range is an exponential moving average across frames.
+    /// `alpha` is the weight kept from history (0 = no smoothing).
+    MinMaxSmoothed { alpha: f32 },
+    /// Input is `(x / 255 - mean) / std` per channel; output is mapped back the same way.
+    MeanStd { mean: [f32; 3], std: [f32; 3] },
+}
+
+/// Applies a `NormalizationMode` to model inputs/outputs, keeping the state the
+/// temporal mode needs between frames.
+#[derive(Debug, Clone)]
+pub struct Normalizer {
+    mode: NormalizationMode,
+    range: Option<(f32, f32)>,
+}
+
+impl Normalizer {
+    pub fn new(mode: NormalizationMode) -> Self {
+        Self { mode, range: None }
+    }
+
+    pub fn mode(&self) -> NormalizationMode {
+        self.mode
+    }
+
+    /// Forgets the smoothed range, e.g. after a seek or a scene change.
+    pub fn reset(&mut self) {
+        self.range = None;
+    }
+
+    /// Scales planar samples in 0-255 into the model's input range, in place.
+    pub fn prepare_input(&self, planes: &mut [f32], channels: usize) {
+        let plane_len = planes.len() / channels.max(1);
+        match self.mode {
+            NormalizationMode::Clamp255 => {}
+            NormalizationMode::MeanStd { mean, std } => {
+                for (i, v) in planes.iter_mut().enumerate() {
+                    let c = (i / plane_len).min(2);
+                    *v = (*v / 255.0 - mean[c]) / std[c];
+                }
+            }
+            _ => planes.iter_mut().for_each(|v| *v /= 255.0),
+        }
+    }
+
+    /// Maps planar model output back to pixel intensity as a fraction of full scale (0-1),
+    /// leaving quantization to the caller's sample type.
+    pub fn finish_output(&mut self, planes: &[f32], channels: usize) -> Vec<f32> {
+        let plane_len = planes.len() / channels.max(1);
+        match self.mode {
+            NormalizationMode::ClampUnit => planes.iter().map(|v| v.clamp(0.0, 1.0)).collect(),
+            NormalizationMode::Clamp255 => planes.iter().map(|v| to_unit(*v)).collect(),
+            NormalizationMode::MeanStd { mean, std } => planes
+                .iter()
+                .enumerate()
+                .map(|(i, v)| {
+                    let c = (i / plane_len).min(2);
+                    (v * std[c] + mean[c]).clamp(0.0, 1.0)
+                })
+                .collect(),
+            NormalizationMode::MinMax => {
+                let (min_v, max_v) = min_max(planes);
+                stretch(planes, min_v, max_v)
+            }
+            NormalizationMode::MinMaxSmoothed { alpha } => {
+                let (min_v, max_v) = min_max(planes);
+                let (min_v, max_v) = match self.range {
+                    Some((pmin, pmax)) => (
+                        alpha * pmin + (1.0 - alpha) * min_v,
+                        alpha * pmax + (1.0 - alpha) * max_v,
+                    ),
+                    None => (min_v, max_v),
+                };
+                self.range = Some((min_v, max_v));
+                stretch(planes, min_v, max_v)
+            }
+        }
+    }
+}
+
+// 0-255 range to 0-1
+fn to_unit(v: f32) -> f32 {
+    (v / 255.0).clamp(0.0, 1.0)
+}
+
+fn min_max(planes: &[f32]) -> (f32, f32) {
+    let mut min_v = f32::MAX;
+    let mut max_v = f32::MIN;
+    for val in planes {
+        if *val < min_v { min_v = *val; }
+        if *val > max_v { max_v = *val; }
+    }
+    (min_v, max_v)
+}
+
+// Rescale from [min_v, max_v] to [0.0, 1.0]
+fn stretch(planes: &[f32], min_v: f32, mut max_v: f32) -> Vec<f32> {
+    // Avoid division by zero
+    if max_v - min_v < 0.00001 {
+        max_v = min_v + 1.0;
+    }
+    planes.iter()
+        .map(|v| ((v - min_v) / (max_v - min_v)).clamp(0.0, 1.0))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len(), "{:?} vs {:?}", actual, expected);
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-5, "{:?} vs {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn clamp_modes_scale_and_clamp() {
+        let mut unit = Normalizer::new(NormalizationMode::ClampUnit);
+        let mut planes = vec![0.0, 127.5, 255.0];
+        unit.prepare_input(&mut planes, 1);
+        assert_close(&planes, &[0.0, 0.5, 1.0]);
+        assert_close(&unit.finish_output(&[-0.5, 0.5, 1.0, 2.0], 1), &[0.0, 0.5, 1.0, 1.0]);
+
+        let mut full = Normalizer::new(NormalizationMode::Clamp255);
+        let mut planes = vec![0.0, 200.0];
+        full.prepare_input(&mut planes, 1);
+        assert_close(&planes, &[0.0, 200.0]);
+        assert_close(&full.finish_output(&[-3.0, 51.0, 300.0], 1), &[0.0, 0.2, 1.0]);
+    }
+
+    #[test]
+    fn mean_std_round_trips_per_channel() {
+        let mode = NormalizationMode::MeanStd { mean: [0.5, 0.4, 0.3], std: [0.2, 0.25, 0.5] };
+        let mut normalizer = Normalizer::new(mode);
+        // Two pixels, three planes
+        let source = [0.0, 255.0, 51.0, 102.0, 153.0, 204.0];
+        let mut planes = source.to_vec();
+        normalizer.prepare_input(&mut planes, 3);
+        assert!((planes[0] - (-2.5)).abs() < 1e-5);
+        assert!((planes[2] - (0.2 - 0.4) / 0.25).abs() < 1e-5);
+        let expected: Vec<f32> = source.iter().map(|v| v / 255.0).collect();
+        assert_close(&normalizer.finish_output(&planes, 3), &expected);
+    }
+
+    #[test]
+    fn min_max_stretches_to_full_range() {
+        let mut normalizer = Normalizer::new(NormalizationMode::MinMax);
+        assert_close(&normalizer.finish_output(&[0.2, 0.4, 0.6], 1), &[0.0, 0.5, 1.0]);
+        // A flat frame does not divide by zero
+        assert_close(&normalizer.finish_output(&[0.5, 0.5], 1), &[0.0, 0.0]);
+    }
+
+    #[test]
+    fn smoothed_range_follows_history_until_reset() {
+        let mut normalizer = Normalizer::new(NormalizationMode::MinMaxSmoothed { alpha: 0.5 });
+        assert_close(&normalizer.finish_output(&[0.0, 1.0], 1), &[0.0, 1.0]);
+        // Half of the old range (0, 1) and half of this frame's (1, 2): (0.5, 1.5)
+        assert_close(&normalizer.finish_output(&[1.0, 2.0], 1), &[0.5, 1.0]);
+        normalizer.reset();
+        assert_close(&normalizer.finish_output(&[1.0, 2.0], 1), &[0.0, 1.0]);
+    }
+}
@@ -0,0 +1,205 @@
+// src/ai/pool.rs
+
+use std::collections::BTreeMap;
+use anyhow::Result;
+use crossbeam_channel::{Receiver, Sender};
+use crate::video::types::{DecoderMsg, EncoderMsg, RawFrame, Sample, UpscaledFrame};
+use crate::video::scene::SceneDetector;
+use crate::error::FirstError;
+use crate::processing::FrameProcessor;
+
+/// AI workers to run when none are configured: physical cores divided by the
+/// threads each session uses for one operator, at least one.
+pub fn default_workers(intra_threads: usize) -> usize {
+    (num_cpus::get_physical() / intra_threads.max(1)).max(1)
+}
+
+/// Numbers decoded frames in arrival order, which is presentation order, marks
+/// scene cuts if there is a `detector`, and hands them to the worker pool. Returns
+/// the number of frames once the decoder is done; dropping `tx_frames` then tells
+/// the workers there is nothing left. Stops early if another stage failed, which
+/// in turn stops the decoder.
+pub fn run_sequencer<T: Sample>(
+    rx_video_raw: Receiver<DecoderMsg<T>>,
+    tx_frames: Sender<(u64, RawFrame<T>)>,
+    errors: &FirstError,
+    mut detector: Option<&mut SceneDetector>,
+) -> u64 {
+    let mut seq = 0u64;
+    for msg in rx_video_raw {
+        if errors.is_set() {
+            break;
+        }
+        match msg {
+            DecoderMsg::Video(mut raw) => {
+                if let Some(detector) = detector.as_deref_mut() {
+                    raw.scene_cut = detector.check(seq, &raw);
+                }
+                if tx_frames.send((seq, raw)).is_err() {
+                    break;
+                }
+                seq += 1;
+            }
+            DecoderMsg::Packet(_) => {},
+            DecoderMsg::EOF => break,
+        }
+    }
+    seq
+}
+
+/// Holds frames finished out of order until every earlier one has arrived.
+pub struct ReorderBuffer<T> {
+    next: u64,
+    pending: BTreeMap<u64, UpscaledFrame<T>>,
+}
+
+impl<T> Default for ReorderBuffer<T> {
+    fn default() -> Self {
+        Self { next: 0, pending: BTreeMap::new() }
+    }
+}
+
+impl<T> ReorderBuffer<T> {
+    /// Stores frame `seq` and returns every frame that is now in order.
+    pub fn push(&mut self, seq: u64, frame: UpscaledFrame<T>) -> Vec<UpscaledFrame<T>> {
+        self.pending.insert(seq, frame);
+        let mut ready = Vec::new();
+        while let Some(frame) = self.pending.remove(&self.next) {
+            ready.push(frame);
+            self.next += 1;
+        }
+        ready
+    }
+
+    /// Whatever is left once all workers are done, oldest first. Only non-empty
+    /// if a worker died without reporting its frames.
+    pub fn drain(&mut self) -> Vec<UpscaledFrame<T>> {
+        std::mem::take(&mut self.pending).into_values().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// Runs the ordered frames between reorder and encoder through `processor`, for steps
+/// that need to see every frame in sequence. Flushes it and passes EOF on at the end;
+/// stops without either if another stage failed.
+pub fn run_ordered<T, P>(mut processor: P, rx: Receiver<EncoderMsg<T>>, tx: Sender<EncoderMsg<T>>, errors: &FirstError) -> Result<()>
+where
+    P: FrameProcessor<UpscaledFrame<T>>,
+{
+    let mut eof = false;
+    for msg in rx {
+        if errors.is_set() {
+            return Ok(());
+        }
+        let frames = match msg {
+            EncoderMsg::Video(frame) => processor.process(vec![frame])?,
+            EncoderMsg::EOF => {
+                eof = true;
+                break;
+            }
+            other => {
+                if tx.send(other).is_err() {
+                    return Ok(());
+                }
+                continue;
+            }
+        };
+        for frame in frames {
+            if tx.send(EncoderMsg::Video(frame)).is_err() {
+                return Ok(());
+            }
+        }
+    }
+    if !eof || errors.is_set() {
+        return Ok(());
+    }
+    for frame in processor.flush()? {
+        if tx.send(EncoderMsg::Video(frame)).is_err() {
+            return Ok(());
+        }
+    }
+    let _ = tx.send(EncoderMsg::EOF);
+    Ok(())
+}
+
+/// Collects worker output and feeds the encoder strictly in frame order, then sends EOF.
+/// If another stage failed it stops without EOF; the encoder then finalizes what it has.
+pub fn run_reorder<T>(rx_done: Receiver<(u64, UpscaledFrame<T>)>, tx_encoder: Sender<EncoderMsg<T>>, errors: &FirstError) {
+    let mut buffer = ReorderBuffer::default();
+    for (seq, frame) in rx_done {
+        if errors.is_set() {
+            return;
+        }
+        for up_frame in buffer.push(seq, frame) {
+            if tx_encoder.send(EncoderMsg::Video(up_frame)).is_err() {
+                return;
+            }
+        }
+    }
+    if errors.is_set() {
+        return;
+    }
+    if !buffer.is_empty() {
+        eprintln!("⚠️ {} frame(s) finished out of order after a worker stopped", buffer.len());
+    }
+    for up_frame in buffer.drain() {
+        if tx_encoder.send(EncoderMsg::Video(up_frame)).is_err() {
+            return;
+        }
+    }
+    let _ = tx_encoder.send(EncoderMsg::EOF);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsmpeg::ffi;
+
+    // One-pixel frame tagged with its sequence number in `pts`
+    fn frame(seq: u64) -> UpscaledFrame<u8> {
+        UpscaledFrame {
+            data: vec![0; 3],
+            width: 1,
+            height: 1,
+            pts: seq as i64,
+            duration: 1,
+            time_base: ffi::AVRational { num: 1, den: 25 },
+            scene_cut: false,
+        }
+    }
+
+    fn pts(frames: Vec<UpscaledFrame<u8>>) -> Vec<i64> {
+        frames.into_iter().map(|f| f.pts).collect()
+    }
+
+    #[test]
+    fn emits_frames_in_sequence_order() {
+        let mut buffer = ReorderBuffer::default();
+        assert!(buffer.push(1, frame(1)).is_empty());
+        assert!(buffer.push(2, frame(2)).is_empty());
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(pts(buffer.push(0, frame(0))), vec![0, 1, 2]);
+        assert_eq!(pts(buffer.push(3, frame(3))), vec![3]);
+        assert!(buffer.push(5, frame(5)).is_empty());
+        assert_eq!(pts(buffer.push(4, frame(4))), vec![4, 5]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn drain_returns_the_gapped_rest_oldest_first() {
+        let mut buffer = ReorderBuffer::default();
+        assert_eq!(pts(buffer.push(0, frame(0))), vec![0]);
+        // Frame 1 never arrives
+        assert!(buffer.push(4, frame(4)).is_empty());
+        assert!(buffer.push(2, frame(2)).is_empty());
+        assert_eq!(pts(buffer.drain()), vec![2, 4]);
+        assert!(buffer.is_empty());
+    }
+}
@@ -1,143 +1,370 @@
 // src/ai/processor.rs
 
 use onnxruntime::{environment::Environment, tensor::OrtOwnedTensor, session::Session};
-use ndarray::{Array4, IxDyn};
-use image::{ImageBuffer, Rgb, Luma, imageops};
-use image::imageops::FilterType;
+use ndarray::IxDyn;
 use anyhow::{Result, anyhow};
 use std::io::Write;
-use crate::video::types::RawFrame;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use crate::video::types::{RawFrame, Sample};
+use crate::video::color::ColorInfo;
+use crate::ai::tiling::{TileConfig, TileBlender, tile_origins, extract_tile};
+use crate::ai::model::ModelInfo;
+use crate::ai::normalize::{NormalizationMode, Normalizer};
+use crate::ai::session::SessionOptions;
+use crate::ai::temporal::FrameWindow;
 
 pub struct AIProcessor<'a> {
     pub session: Session<'a>,
+    pub info: ModelInfo,
+    tiling: Option<TileConfig>,
+    normalizer: Normalizer,
+    luma_weights: [f32; 3],
+    batch_size: usize,
+    window: Option<FrameWindow<Arc<[f32]>>>, // model input planes of recent frames, temporal models only
+    window_size: (u32, u32),                 // input size of the frames in `window`
+    scene_cuts: VecDeque<bool>,              // `scene_cut` of each frame not yet returned, in order
 }
 
 impl<'a> AIProcessor<'a> {
-    pub fn new(model_path: &'a str, env: &'a Environment) -> Result<Self> {
-        let session = env.new_session_builder()?.with_model_from_file(model_path).map_err(|e| anyhow!("{:?}", e))?;
-        Ok(Self { session })
+    /// Opens its own session on `env`, built with `options`.
+    pub fn new(model_path: &'a str, env: &'a Environment, options: &SessionOptions) -> Result<Self> {
+        let mut session = options.build(env, model_path)?;
+        let info = ModelInfo::from_session(&mut session)?;
+        if info.channels != info.output_channels {
+            return Err(anyhow!("Model input and output channel counts must match, model is {}", info));
+        }
+        let window = info.frames.map(|t| FrameWindow::new(t as usize));
+        Ok(Self {
+            session,
+            info,
+            tiling: None,
+            normalizer: Normalizer::new(NormalizationMode::default()),
+            luma_weights: ColorInfo::default().luma_weights(),
+            batch_size: 1,
+            window,
+            window_size: (0, 0),
+            scene_cuts: VecDeque::new(),
+        })
+    }
+
+    /// Switches to tiled full-resolution inference. The tile geometry is checked
+    /// against the model's input shape here so a bad config fails before decoding starts.
+    pub fn with_tiling(mut self, tile: TileConfig) -> Result<Self> {
+        if self.is_temporal() {
+            return Err(anyhow!("Tiling is not supported for temporal models, model is {}", self.info));
+        }
+        self.tiling = Some(tile.resolve(self.info.input_size)?);
+        Ok(self)
+    }
+
+    /// Selects how frames are scaled into the model and how its output is mapped back to pixels.
+    pub fn with_normalization(mut self, mode: NormalizationMode) -> Self {
+        self.normalizer = Normalizer::new(mode);
+        self
+    }
+
+    /// Uses the source's YUV matrix to derive luma for luma-only models.
+    pub fn with_color(mut self, color: &ColorInfo) -> Self {
+        self.luma_weights = color.luma_weights();
+        self
+    }
+
+    /// Runs up to `n` frames (or tiles) per inference. Models with a fixed batch
+    /// dimension clamp `n` to it and get partial batches zero-padded.
+    pub fn with_batch_size(mut self, n: usize) -> Self {
+        self.batch_size = match self.info.batch {
+            Some(b) => n.clamp(1, b as usize),
+            None => n.max(1),
+        };
+        self
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /// True when the model upscales each frame from a window of neighboring frames.
+    /// Its results then lag behind the input until the later neighbors arrive.
+    pub fn is_temporal(&self) -> bool {
+        self.window.is_some()
+    }
+
+    /// True when the model takes and returns full-color RGB rather than luma only.
+    pub fn is_rgb(&self) -> bool {
+        self.info.channels == 3
+    }
+
+    /// Returns the AI luma plane together with its width and height.
+    pub fn process_frame_y<T: Sample>(&mut self, frame: &RawFrame<T>) -> Result<(Vec<T>, i32, i32)> {
+        self.process_batch_y(std::slice::from_ref(frame))?.into_iter().next()
+            .ok_or_else(|| anyhow!("Temporal models need the frames that follow, use process_batch_y"))
+    }
+
+    /// Returns the AI output as interleaved RGB together with its width and height.
+    pub fn process_frame_rgb<T: Sample>(&mut self, frame: &RawFrame<T>) -> Result<(Vec<T>, i32, i32)> {
+        self.process_batch_rgb(std::slice::from_ref(frame))?.into_iter().next()
+            .ok_or_else(|| anyhow!("Temporal models need the frames that follow, use process_batch_rgb"))
+    }
+
+    /// Like `process_frame_y` for several frames, batched into as few inferences as possible.
+    /// Results come back in input order; for a temporal model only those of frames whose
+    /// later neighbors have arrived, the rest come from `flush_y`.
+    pub fn process_batch_y<T: Sample>(&mut self, frames: &[RawFrame<T>]) -> Result<Vec<(Vec<T>, i32, i32)>> {
+        if self.is_rgb() {
+            return Err(anyhow!("Model is RGB, use process_batch_rgb"));
+        }
+        let outputs = self.run_batch(frames)?;
+        Ok(self.finish_y(outputs))
+    }
+
+    /// Like `process_frame_rgb` for several frames, batched into as few inferences as possible.
+    /// Results come back in input order; for a temporal model only those of frames whose
+    /// later neighbors have arrived, the rest come from `flush_rgb`.
+    pub fn process_batch_rgb<T: Sample>(&mut self, frames: &[RawFrame<T>]) -> Result<Vec<(Vec<T>, i32, i32)>> {
+        if !self.is_rgb() {
+            return Err(anyhow!("Model is luma-only, use process_batch_y"));
+        }
+        let outputs = self.run_batch(frames)?;
+        Ok(self.finish_rgb(outputs))
+    }
+
+    /// Ends the stream for a temporal model: returns the luma results of the frames
+    /// still held back, with the last frame repeated as their later neighbors.
+    pub fn flush_y<T: Sample>(&mut self) -> Result<Vec<(Vec<T>, i32, i32)>> {
+        let outputs = self.flush_window()?;
+        Ok(self.finish_y(outputs))
+    }
+
+    /// Like `flush_y` for RGB models.
+    pub fn flush_rgb<T: Sample>(&mut self) -> Result<Vec<(Vec<T>, i32, i32)>> {
+        let outputs = self.flush_window()?;
+        Ok(self.finish_rgb(outputs))
+    }
+
+    fn finish_y<T: Sample>(&mut self, outputs: Vec<(Vec<f32>, usize, usize)>) -> Vec<(Vec<T>, i32, i32)> {
+        outputs.into_iter().map(|(plane, w, h)| {
+            self.start_output();
+            let pixels = self.normalizer.finish_output(&plane, 1).into_iter().map(T::from_unit).collect();
+            (pixels, w as i32, h as i32)
+        }).collect()
+    }
+
+    fn finish_rgb<T: Sample>(&mut self, outputs: Vec<(Vec<f32>, usize, usize)>) -> Vec<(Vec<T>, i32, i32)> {
+        outputs.into_iter().map(|(planes, w, h)| {
+            self.start_output();
+            let pixels: Vec<T> = self.normalizer.finish_output(&planes, 3).into_iter().map(T::from_unit).collect();
+            (planar_to_interleaved(&pixels, w * h), w as i32, h as i32)
+        }).collect()
+    }
+
+    // Outputs come back in input order, possibly later (temporal models), so the output
+    // range of a smoothed normalizer is forgotten when the first frame of a new scene
+    // is finished rather than when it goes in
+    fn start_output(&mut self) {
+        if self.scene_cuts.pop_front().unwrap_or(false) {
+            self.normalizer.reset();
+        }
+    }
+
+    /// Runs the model over frames and returns planar float output with its width and height, per frame.
+    fn run_batch<T: Sample>(&mut self, frames: &[RawFrame<T>]) -> Result<Vec<(Vec<f32>, usize, usize)>> {
+        self.scene_cuts.extend(frames.iter().map(|frame| frame.scene_cut));
+        if self.is_temporal() {
+            return self.process_temporal(frames);
+        }
+        match self.tiling {
+            // Tiles of one frame are batched together
+            Some(tile) => frames.iter().map(|frame| self.process_tiled(frame, tile)).collect(),
+            None => self.process_whole(frames),
+        }
     }
 
-    pub fn process_frame_y(&mut self, frame: &RawFrame) -> Result<Vec<u8>> {
-        let (tw, th) = (224, 224); // Model Input Size
-        
-        // 1. High Quality Downscaling of Input (RGB) -> 224x224
+    fn process_whole<T: Sample>(&mut self, frames: &[RawFrame<T>]) -> Result<Vec<(Vec<f32>, usize, usize)>> {
+        let Some(first) = frames.first() else { return Ok(Vec::new()) };
+        let (tw, th) = self.input_size_for(first);
+
+        let mut inputs = Vec::with_capacity(frames.len());
+        for frame in frames {
+            inputs.push(self.prepare_input(frame, tw, th)?);
+        }
+
+        self.infer(inputs, tw as usize, th as usize)
+    }
+
+    // Fixed-size models get the frame resized to their input, dynamic ones see it as-is
+    fn input_size_for<T: Sample>(&self, frame: &RawFrame<T>) -> (u32, u32) {
+        self.info.input_size.unwrap_or((frame.width as u32, frame.height as u32))
+    }
+
+    // Normalized model input planes of one frame at `tw`x`th`
+    fn prepare_input<T: Sample>(&self, frame: &RawFrame<T>, tw: u32, th: u32) -> Result<Vec<f32>> {
+        let channels = self.info.channels as usize;
+        // 1. High Quality Downscaling of Input (RGB) -> Model Input Size
         // Use Image crate for this to avoid Aliasing from Nearest Neighbor
-        let mut input_tensor_data = Vec::with_capacity((tw * th) as usize);
-        
-        if let Some(img) = ImageBuffer::<Rgb<u8>, Vec<u8>>::from_raw(frame.width as u32, frame.height as u32, frame.data.clone()) {
-            let resized = imageops::resize(&img, tw as u32, th as u32, FilterType::CatmullRom); // Bicubic Downscale
-            
-            // Convert to Y-Channel Tensor (0-1)
-            for pixel in resized.pixels() {
-                let r = pixel[0] as f32;
-                let g = pixel[1] as f32;
-                let b = pixel[2] as f32;
-                let y_val = (0.299 * r + 0.587 * g + 0.114 * b) / 255.0;
-                input_tensor_data.push(y_val);
-            }
+        let mut input_tensor_data = if (tw, th) == (frame.width as u32, frame.height as u32) {
+            rgb_to_planes(&frame.data, channels, self.luma_weights)
+        } else if self.info.input_size.is_some() {
+            let resized = T::resize_rgb(&frame.data, frame.width as u32, frame.height as u32, tw, th); // Bicubic Downscale
+            rgb_to_planes(&resized, channels, self.luma_weights)
         } else {
-             // Fallback to zeros if image load fails
-             input_tensor_data = vec![0.0; (tw * th) as usize];
+            return Err(anyhow!("Frames of one batch must share a size, got {}x{} and {}x{}", tw, th, frame.width, frame.height));
+        };
+        self.normalizer.prepare_input(&mut input_tensor_data, channels);
+        Ok(input_tensor_data)
+    }
+
+    // Adds frames to the window and runs the model on every window they complete
+    fn process_temporal<T: Sample>(&mut self, frames: &[RawFrame<T>]) -> Result<Vec<(Vec<f32>, usize, usize)>> {
+        let mut results = Vec::new();
+        let mut windows = Vec::new();
+        for frame in frames {
+            // Neighbors from another scene would only blur it, the cut is treated like an end of stream
+            if frame.scene_cut {
+                if let Some(window) = &mut self.window {
+                    windows.extend(window.finish());
+                }
+            }
+            let (tw, th) = self.input_size_for(frame);
+            let Some(window) = &self.window else { return Err(anyhow!("Model is not temporal")) };
+            if window.pending() > 0 && (tw, th) != self.window_size {
+                return Err(anyhow!("Frames of one window must share a size, got {}x{} and {}x{}",
+                    self.window_size.0, self.window_size.1, tw, th));
+            }
+            // Windows collected so far are packed at the size they were prepared at
+            if (tw, th) != self.window_size && !windows.is_empty() {
+                results.extend(self.infer_windows(std::mem::take(&mut windows))?);
+            }
+            let planes = self.prepare_input(frame, tw, th)?;
+            self.window_size = (tw, th);
+            if let Some(window) = &mut self.window {
+                windows.extend(window.push(Arc::from(planes)));
+            }
         }
+        results.extend(self.infer_windows(windows)?);
+        Ok(results)
+    }
+
+    fn flush_window(&mut self) -> Result<Vec<(Vec<f32>, usize, usize)>> {
+        let windows = match &mut self.window {
+            Some(window) => window.finish(),
+            None => Vec::new(),
+        };
+        self.infer_windows(windows)
+    }
+
+    fn process_tiled<T: Sample>(&mut self, frame: &RawFrame<T>, tile: TileConfig) -> Result<(Vec<f32>, usize, usize)> {
+        let (w, h) = (frame.width as usize, frame.height as usize);
+        let t = tile.size as usize;
+        let overlap = tile.overlap as usize;
+        let channels = self.info.channels as usize;
+        let mut planes = rgb_to_planes(&frame.data, channels, self.luma_weights);
+        self.normalizer.prepare_input(&mut planes, channels);
+
+        let xs = tile_origins(w, t, overlap);
+        let ys = tile_origins(h, t, overlap);
+
+        let origins: Vec<(usize, usize)> = ys.iter().flat_map(|&y0| xs.iter().map(move |&x0| (x0, y0))).collect();
 
-        let tensor = Array4::from_shape_vec((1, 1, th as usize, tw as usize), input_tensor_data)?;
-        let outputs: Vec<OrtOwnedTensor<f32, IxDyn>> = self.session.run(vec![tensor])?;
-        let tensor_out = &outputs[0];
-        let shape = tensor_out.shape();
-        
-        let mut pixels = Vec::with_capacity(shape[2] * shape[3]);
-        
-        // Output normalization check
-        // User reports "faint" image. We implement Auto-Contrast (Min-Max Normalization).
-        let mut min_v = f32::MAX;
-        let mut max_v = f32::MIN;
-        
-        // Pass 1: Find Range
-        for val in tensor_out.iter() {
-            if *val < min_v { min_v = *val; }
-            if *val > max_v { max_v = *val; }
+        let scale = self.info.scale as usize;
+        let mut blender = TileBlender::new(w * scale, h * scale, self.info.output_channels as usize);
+        for batch in origins.chunks(self.batch_size) {
+            let patches = batch.iter().map(|&(x0, y0)| extract_tile(&planes, channels, w, h, x0, y0, t)).collect();
+            for ((out, ow, oh), &(x0, y0)) in self.infer(patches, t, t)?.into_iter().zip(batch) {
+                if (ow, oh) != (t * scale, t * scale) {
+                    return Err(anyhow!("Model returned a {}x{} tile, expected {}x{}", ow, oh, t * scale, t * scale));
+                }
+                blender.add(&out, ow, oh, x0 * scale, y0 * scale, overlap * scale);
+            }
         }
-        
-        // Avoid division by zero
-        if max_v - min_v < 0.00001 {
-            max_v = min_v + 1.0; 
+
+        Ok((blender.finish(), w * scale, h * scale))
+    }
+
+    /// Runs planar `w`x`h` inputs through the model in its tensor layout, `batch_size`
+    /// at a time, and returns each planar output with its width and height, in order.
+    fn infer(&mut self, inputs: Vec<Vec<f32>>, w: usize, h: usize) -> Result<Vec<(Vec<f32>, usize, usize)>> {
+        let layout = self.info.layout;
+        let mut results = Vec::with_capacity(inputs.len());
+        for chunk in inputs.chunks(self.batch_size) {
+            let batch = self.info.batch.map_or(chunk.len(), |b| b as usize);
+            let tensor = layout.pack(chunk, batch, self.info.channels as usize, h, w)?;
+            let outputs: Vec<OrtOwnedTensor<f32, IxDyn>> = self.session.run(vec![tensor])?;
+            let tensor_out = &outputs[0];
+            let (oc, oh, ow) = layout.chw(tensor_out.shape())?;
+            let flat: Vec<f32> = tensor_out.iter().cloned().collect();
+            for i in 0..chunk.len() {
+                results.push((layout.unpack(&flat, i, oc, oh, ow), ow, oh));
+            }
         }
+        Ok(results)
+    }
 
-        // Pass 2: Normalize to 0-255
-        for y in 0..shape[2] {
-            for x in 0..shape[3] {
-                let v = tensor_out[[0, 0, y, x]];
-                // Rescale v from [min_v, max_v] to [0.0, 255.0]
-                let normalized = (v - min_v) / (max_v - min_v); 
-                let pixel = (normalized * 255.0).clamp(0.0, 255.0) as u8;
-                pixels.push(pixel);
+    /// Runs frame windows through the model, `batch_size` windows at a time, and returns
+    /// the planar output for each window's center frame with its width and height, in order.
+    fn infer_windows(&mut self, windows: Vec<Vec<Arc<[f32]>>>) -> Result<Vec<(Vec<f32>, usize, usize)>> {
+        let Some(window) = &self.window else { return Ok(Vec::new()) };
+        let (frames, center) = (window.size(), window.center());
+        let (w, h) = (self.window_size.0 as usize, self.window_size.1 as usize);
+        let layout = self.info.layout;
+        // Models that return the whole window are read at its center
+        let (stride, offset) = if self.info.output_window { (frames, center) } else { (1, 0) };
+        let mut results = Vec::with_capacity(windows.len());
+        for chunk in windows.chunks(self.batch_size) {
+            let batch = self.info.batch.map_or(chunk.len(), |b| b as usize);
+            let tensor = layout.pack_windows(chunk, batch, frames, self.info.channels as usize, h, w)?;
+            let outputs: Vec<OrtOwnedTensor<f32, IxDyn>> = self.session.run(vec![tensor])?;
+            let tensor_out = &outputs[0];
+            let (oc, oh, ow) = layout.chw(tensor_out.shape())?;
+            let flat: Vec<f32> = tensor_out.iter().cloned().collect();
+            for i in 0..chunk.len() {
+                results.push((layout.unpack(&flat, i * stride + offset, oc, oh, ow), ow, oh));
             }
         }
-        Ok(pixels)
+        Ok(results)
+    }
+}
+
+// Convert interleaved RGB to planar float samples on a 0-255 scale (fractional for
+// high bit depth): a Y-Channel plane for luma models, R/G/B planes for color models
+fn rgb_to_planes<T: Sample>(rgb: &[T], channels: usize, luma_weights: [f32; 3]) -> Vec<f32> {
+    if channels == 1 {
+        let [kr, kg, kb] = luma_weights;
+        return rgb.chunks_exact(3)
+            .map(|p| (kr * p[0].to_unit() + kg * p[1].to_unit() + kb * p[2].to_unit()) * 255.0)
+            .collect();
+    }
+    let pixels = rgb.len() / 3;
+    let mut planes = vec![0.0; pixels * 3];
+    for (i, p) in rgb.chunks_exact(3).enumerate() {
+        planes[i] = p[0].to_unit() * 255.0;
+        planes[pixels + i] = p[1].to_unit() * 255.0;
+        planes[2 * pixels + i] = p[2].to_unit() * 255.0;
     }
+    planes
+}
+
+// Interleave planar R/G/B samples back into packed RGB
+fn planar_to_interleaved<T: Sample>(planes: &[T], pixels: usize) -> Vec<T> {
+    let mut rgb = Vec::with_capacity(pixels * 3);
+    for i in 0..pixels {
+        rgb.push(planes[i]);
+        rgb.push(planes[pixels + i]);
+        rgb.push(planes[2 * pixels + i]);
+    }
+    rgb
 }
 
 // --- SCALING LOGIC (High Quality) ---
 
 // Upsaling 1-channel Grayscale (Y-Plane) using Cubic Interpolation
-pub fn upscale_grayscale(src: &[u8], sw: i32, sh: i32, dw: i32, dh: i32) -> Vec<u8> {
-    // 1. Create ImageBuffer from src
-    if let Some(img) = ImageBuffer::<Luma<u8>, Vec<u8>>::from_raw(sw as u32, sh as u32, src.to_vec()) {
-        // 2. Resize
-        let resized = imageops::resize(&img, dw as u32, dh as u32, FilterType::CatmullRom);
-        // 3. Return raw bytes
-        return resized.into_raw();
-    }
-    // Fallback (should not happen)
-    vec![0u8; (dw * dh) as usize]
+pub fn upscale_grayscale<T: Sample>(src: &[T], sw: i32, sh: i32, dw: i32, dh: i32) -> Vec<T> {
+    T::resize_luma(src, sw as u32, sh as u32, dw as u32, dh as u32)
 }
 
 // Resizing RGB using Cubic Interpolation
-pub fn upscale_to_original(rgb: &[u8], src_w: i32, src_h: i32, target_w: i32, target_h: i32) -> Vec<u8> {
-    if let Some(img) = ImageBuffer::<Rgb<u8>, Vec<u8>>::from_raw(src_w as u32, src_h as u32, rgb.to_vec()) {
-        let resized = imageops::resize(&img, target_w as u32, target_h as u32, FilterType::CatmullRom);
-        return resized.into_raw();
-    }
-    vec![0u8; (target_w * target_h * 3) as usize]
-}
-
-// Simple RGB to YUV420P Converter
-pub fn rgb_to_yuv420p(rgb: &[u8], w: i32, h: i32) -> Vec<u8> {
-    let y_size = (w * h) as usize;
-    let uv_size = (w / 2 * h / 2) as usize;
-    let mut yuv = vec![0u8; y_size + uv_size * 2];
-    
-    // Y Plane
-    for y in 0..h {
-        for x in 0..w {
-            let idx = (y * w + x) as usize;
-            let r = rgb[idx*3] as f32;
-            let g = rgb[idx*3+1] as f32;
-            let b = rgb[idx*3+2] as f32;
-            
-            yuv[idx] = (0.299*r + 0.587*g + 0.114*b).clamp(0.0, 255.0) as u8;
-        }
-    }
-    
-    // U and V Planes (Subsampled)
-    for y in 0..h/2 {
-        for x in 0..w/2 {
-            let src_x = x * 2;
-            let src_y = y * 2;
-            let idx = ((src_y * w + src_x) * 3) as usize;
-             let r = rgb[idx] as f32;
-            let g = rgb[idx+1] as f32;
-            let b = rgb[idx+2] as f32;
-            
-            let u = (-0.14713 * r - 0.28886 * g + 0.436 * b + 128.0).clamp(0.0, 255.0) as u8;
-            let v = (0.615 * r - 0.51499 * g - 0.10001 * b + 128.0).clamp(0.0, 255.0) as u8;
-            
-            yuv[y_size + (y as usize * (w/2) as usize + x as usize)] = u;
-            yuv[y_size + uv_size + (y as usize * (w/2) as usize + x as usize)] = v;
-        }
-    }
-    yuv
+pub fn upscale_to_original<T: Sample>(rgb: &[T], src_w: i32, src_h: i32, target_w: i32, target_h: i32) -> Vec<T> {
+    T::resize_rgb(rgb, src_w as u32, src_h as u32, target_w as u32, target_h as u32)
 }
 
 // --- DEBUG HELPER ---
@@ -0,0 +1,60 @@
+// src/ai/session.rs
+
+use onnxruntime::{environment::Environment, session::Session, GraphOptimizationLevel};
+use anyhow::{Result, anyhow};
+
+/// Graph rewrites ONNX Runtime applies when loading the model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OptimizationLevel {
+    Disabled,
+    Basic,
+    Extended,
+    #[default]
+    All,
+}
+
+impl OptimizationLevel {
+    fn to_ort(self) -> GraphOptimizationLevel {
+        match self {
+            OptimizationLevel::Disabled => GraphOptimizationLevel::DisableAll,
+            OptimizationLevel::Basic => GraphOptimizationLevel::Basic,
+            OptimizationLevel::Extended => GraphOptimizationLevel::Extended,
+            OptimizationLevel::All => GraphOptimizationLevel::All,
+        }
+    }
+}
+
+/// How each AI session is built.
+///
+/// The binding only exposes the intra-op thread count and the graph optimization
+/// level, so those are the only knobs; sessions always run on the CPU provider.
+/// Unlike ONNX Runtime, which gives one session every core, `intra_threads`
+/// defaults to 1: parallelism comes from running one session per worker.
+#[derive(Debug, Clone)]
+pub struct SessionOptions {
+    pub intra_threads: usize, // threads one operator may use
+    pub optimization: OptimizationLevel,
+}
+
+impl Default for SessionOptions {
+    fn default() -> Self {
+        Self {
+            intra_threads: 1,
+            optimization: OptimizationLevel::default(),
+        }
+    }
+}
+
+impl SessionOptions {
+    /// Builds a session for `model_path` on `env` with these options.
+    pub fn build<'a>(&self, env: &'a Environment, model_path: &'a str) -> Result<Session<'a>> {
+        let threads = i16::try_from(self.intra_threads.max(1))
+            .map_err(|_| anyhow!("Too many intra-op threads: {}", self.intra_threads))?;
+        let session = env.new_session_builder()?
+            .with_number_threads(threads)?
+            .with_optimization_level(self.optimization.to_ort())?
+            .with_model_from_file(model_path)
+            .map_err(|e| anyhow!("{:?}", e))?;
+        Ok(session)
+    }
+}
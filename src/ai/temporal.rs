@@ -0,0 +1,124 @@
+// src/ai/temporal.rs
+
+use std::collections::VecDeque;
+
+/// Frames per window for temporal models whose time axis is dynamic.
+pub const DEFAULT_WINDOW: u32 = 5;
+
+/// Sliding window over consecutive frames for models that upscale a frame from its
+/// neighbors. Each window holds `before` frames, the center frame and `after`
+/// frames; at the start and end of the stream the missing neighbors are copies of
+/// the first and last frame. Items are cloned into every window they are part of,
+/// so they should be cheap to clone.
+pub struct FrameWindow<I> {
+    before: usize,
+    after: usize,
+    frames: VecDeque<I>, // starts at the first frame of the next window
+}
+
+impl<I: Clone> FrameWindow<I> {
+    /// Window of `len` frames centered on the middle one (the earlier of the two
+    /// middle ones for even lengths).
+    pub fn new(len: usize) -> Self {
+        let len = len.max(1);
+        let before = (len - 1) / 2;
+        Self { before, after: len - 1 - before, frames: VecDeque::with_capacity(len) }
+    }
+
+    /// Frames per window.
+    pub fn size(&self) -> usize {
+        self.before + 1 + self.after
+    }
+
+    /// Position of the center frame within a window.
+    pub fn center(&self) -> usize {
+        self.before
+    }
+
+    /// Frames held back, waiting for their later neighbors.
+    pub fn pending(&self) -> usize {
+        self.frames.len().saturating_sub(self.before)
+    }
+
+    /// Adds the next frame and returns the windows it completes, oldest first:
+    /// the one centered `after` frames back, if there is one.
+    pub fn push(&mut self, frame: I) -> Vec<Vec<I>> {
+        if self.frames.is_empty() {
+            self.frames.extend(std::iter::repeat_n(frame.clone(), self.before));
+        }
+        self.frames.push_back(frame);
+        self.take_windows()
+    }
+
+    /// Ends the stream: returns the windows of every frame still held back, padded
+    /// with copies of the last frame. The next `push` starts a new stream.
+    pub fn finish(&mut self) -> Vec<Vec<I>> {
+        let Some(last) = self.frames.back().cloned() else { return Vec::new() };
+        self.frames.extend(std::iter::repeat_n(last, self.after));
+        let windows = self.take_windows();
+        self.frames.clear();
+        windows
+    }
+
+    fn take_windows(&mut self) -> Vec<Vec<I>> {
+        let len = self.size();
+        let mut windows = Vec::new();
+        while self.frames.len() >= len {
+            windows.push(self.frames.iter().take(len).cloned().collect());
+            self.frames.pop_front();
+        }
+        windows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_are_padded_at_both_ends() {
+        let mut window = FrameWindow::new(5);
+        assert_eq!((window.size(), window.center()), (5, 2));
+        let mut windows = Vec::new();
+        for frame in 0..4 {
+            windows.extend(window.push(frame));
+        }
+        // The first frame's window needs two frames after it
+        assert_eq!(windows, vec![vec![0, 0, 0, 1, 2], vec![0, 0, 1, 2, 3]]);
+        assert_eq!(window.pending(), 2);
+        assert_eq!(window.finish(), vec![vec![0, 1, 2, 3, 3], vec![1, 2, 3, 3, 3]]);
+        assert_eq!(window.pending(), 0);
+    }
+
+    #[test]
+    fn finish_starts_a_new_stream() {
+        let mut window = FrameWindow::new(3);
+        assert!(window.push(1).is_empty());
+        assert_eq!(window.finish(), vec![vec![1, 1, 1]]);
+        assert!(window.finish().is_empty());
+        // No frames of the first stream leak into the second
+        assert!(window.push(7).is_empty());
+        assert_eq!(window.push(8), vec![vec![7, 7, 8]]);
+        assert_eq!(window.finish(), vec![vec![7, 8, 8]]);
+    }
+
+    #[test]
+    fn even_windows_center_on_the_earlier_middle_frame() {
+        let mut window = FrameWindow::new(4);
+        assert_eq!(window.center(), 1);
+        let mut windows = window.push(0);
+        windows.extend(window.push(1));
+        windows.extend(window.push(2));
+        windows.extend(window.finish());
+        assert_eq!(windows, vec![vec![0, 0, 1, 2], vec![0, 1, 2, 2], vec![1, 2, 2, 2]]);
+    }
+
+    #[test]
+    fn single_frame_windows_pass_frames_through() {
+        let mut window = FrameWindow::new(0);
+        assert_eq!(window.size(), 1);
+        assert_eq!(window.push(3), vec![vec![3]]);
+        assert_eq!(window.pending(), 0);
+        assert!(window.finish().is_empty());
+    }
+}
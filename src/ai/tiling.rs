@@ -0,0 +1,216 @@
+// src/ai/tiling.rs
+
+use anyhow::{Result, anyhow};
+
+/// Tiled inference settings. A `size` of 0 means "use the model's native input size".
+#[derive(Debug, Clone, Copy)]
+pub struct TileConfig {
+    pub size: u32,
+    pub overlap: u32,
+}
+
+impl Default for TileConfig {
+    fn default() -> Self {
+        Self { size: 0, overlap: 16 }
+    }
+}
+
+impl TileConfig {
+    /// Validates the tile geometry against the model's spatial input size
+    /// (`None` = dynamic) and returns the config with the effective tile size filled in.
+    pub fn resolve(&self, model_size: Option<(u32, u32)>) -> Result<TileConfig> {
+        let size = match model_size {
+            Some((mw, mh)) => {
+                if mh != mw {
+                    return Err(anyhow!("Tiling needs a square model input, model expects {}x{}", mw, mh));
+                }
+                if self.size != 0 && self.size != mh {
+                    return Err(anyhow!("Tile size {} does not match the model's fixed input size {}", self.size, mh));
+                }
+                mh
+            }
+            None => {
+                if self.size == 0 {
+                    return Err(anyhow!("Model has a dynamic input size, an explicit tile size is required"));
+                }
+                self.size
+            }
+        };
+
+        if self.overlap >= size {
+            return Err(anyhow!("Tile overlap {} must be smaller than the tile size {}", self.overlap, size));
+        }
+        Ok(TileConfig { size, overlap: self.overlap })
+    }
+}
+
+/// Top-left offsets of tiles covering `len` pixels. The last tile is pulled back
+/// so it ends flush with the edge instead of running past it.
+pub fn tile_origins(len: usize, tile: usize, overlap: usize) -> Vec<usize> {
+    if len <= tile {
+        return vec![0];
+    }
+    let stride = tile - overlap;
+    let mut origins = Vec::new();
+    let mut pos = 0;
+    loop {
+        if pos + tile >= len {
+            origins.push(len - tile);
+            break;
+        }
+        origins.push(pos);
+        pos += stride;
+    }
+    origins
+}
+
+/// Copies a `tile`x`tile` patch out of planar `channels`x`h`x`w` data, replicating the
+/// edge pixels when the patch runs past the frame (frames smaller than a tile).
+pub fn extract_tile(planes: &[f32], channels: usize, w: usize, h: usize, x0: usize, y0: usize, tile: usize) -> Vec<f32> {
+    let mut patch = Vec::with_capacity(channels * tile * tile);
+    for c in 0..channels {
+        let plane = &planes[c * w * h..(c + 1) * w * h];
+        for ty in 0..tile {
+            let sy = (y0 + ty).min(h - 1);
+            for tx in 0..tile {
+                let sx = (x0 + tx).min(w - 1);
+                patch.push(plane[sy * w + sx]);
+            }
+        }
+    }
+    patch
+}
+
+/// Blend weights along one tile axis: a linear ramp over `feather` pixels at both ends.
+/// Weights never reach zero, so pixels only one tile covers (frame borders) keep their value.
+pub fn feather_ramp(n: usize, feather: usize) -> Vec<f32> {
+    (0..n)
+        .map(|i| {
+            if feather == 0 {
+                return 1.0;
+            }
+            let d = i.min(n - 1 - i) as f32 + 1.0;
+            (d / (feather as f32 + 1.0)).min(1.0)
+        })
+        .collect()
+}
+
+/// Accumulates overlapping planar output tiles into one frame using feathered weights.
+pub struct TileBlender {
+    width: usize,
+    height: usize,
+    channels: usize,
+    acc: Vec<f32>,
+    weight: Vec<f32>,
+}
+
+impl TileBlender {
+    pub fn new(width: usize, height: usize, channels: usize) -> Self {
+        Self {
+            width,
+            height,
+            channels,
+            acc: vec![0.0; channels * width * height],
+            weight: vec![0.0; width * height],
+        }
+    }
+
+    /// Adds a planar `tw`x`th` tile whose top-left lands at (x0, y0). Pixels outside the canvas are dropped.
+    pub fn add(&mut self, tile: &[f32], tw: usize, th: usize, x0: usize, y0: usize, feather: usize) {
+        let ramp_x = feather_ramp(tw, feather);
+        let ramp_y = feather_ramp(th, feather);
+        let plane_len = self.width * self.height;
+        for ty in 0..th {
+            let y = y0 + ty;
+            if y >= self.height {
+                break;
+            }
+            for tx in 0..tw {
+                let x = x0 + tx;
+                if x >= self.width {
+                    break;
+                }
+                let wgt = ramp_x[tx].min(ramp_y[ty]);
+                let idx = y * self.width + x;
+                for c in 0..self.channels {
+                    self.acc[c * plane_len + idx] += tile[(c * th + ty) * tw + tx] * wgt;
+                }
+                self.weight[idx] += wgt;
+            }
+        }
+    }
+
+    pub fn finish(self) -> Vec<f32> {
+        let plane_len = self.width * self.height;
+        self.acc
+            .iter()
+            .enumerate()
+            .map(|(i, a)| {
+                let w = self.weight[i % plane_len];
+                if w > 0.0 { a / w } else { 0.0 }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tile_origins_cover_the_frame() {
+        assert_eq!(tile_origins(64, 64, 16), vec![0]);
+        assert_eq!(tile_origins(40, 64, 16), vec![0]);
+        assert_eq!(tile_origins(112, 64, 16), vec![0, 48]);
+        // The last tile is pulled back to end at the edge
+        assert_eq!(tile_origins(130, 64, 16), vec![0, 48, 66]);
+        for len in [65, 100, 177, 1080] {
+            let origins = tile_origins(len, 64, 16);
+            assert_eq!(origins[0], 0);
+            assert_eq!(*origins.last().unwrap() + 64, len);
+            for pair in origins.windows(2) {
+                assert!(pair[1] > pair[0] && pair[1] - pair[0] <= 48, "{:?} for {}", origins, len);
+            }
+        }
+    }
+
+    #[test]
+    fn feather_ramp_is_symmetric_and_never_zero() {
+        let ramp = feather_ramp(8, 3);
+        assert_eq!(ramp, vec![0.25, 0.5, 0.75, 1.0, 1.0, 0.75, 0.5, 0.25]);
+        assert!(feather_ramp(8, 0).iter().all(|&w| w == 1.0));
+    }
+
+    #[test]
+    fn blended_weights_sum_to_one() {
+        // Constant tiles over overlapping origins must come out unchanged everywhere
+        let (w, h, tile, overlap) = (100, 70, 32, 8);
+        let mut blender = TileBlender::new(w, h, 2);
+        let patch: Vec<f32> = [0.25; 32 * 32].into_iter().chain([0.75; 32 * 32]).collect();
+        for y0 in tile_origins(h, tile, overlap) {
+            for x0 in tile_origins(w, tile, overlap) {
+                blender.add(&patch, tile, tile, x0, y0, overlap);
+            }
+        }
+        let out = blender.finish();
+        assert!(out[..w * h].iter().all(|v| (v - 0.25).abs() < 1e-6));
+        assert!(out[w * h..].iter().all(|v| (v - 0.75).abs() < 1e-6));
+    }
+
+    #[test]
+    fn extract_tile_replicates_edges() {
+        // 1 channel, 2x2 frame
+        let planes = [1.0, 2.0, 3.0, 4.0];
+        assert_eq!(extract_tile(&planes, 1, 2, 2, 0, 0, 3), vec![1.0, 2.0, 2.0, 3.0, 4.0, 4.0, 3.0, 4.0, 4.0]);
+    }
+
+    #[test]
+    fn resolve_checks_the_model_size() {
+        let tile = TileConfig::default().resolve(Some((128, 128))).unwrap();
+        assert_eq!((tile.size, tile.overlap), (128, 16));
+        assert!(TileConfig::default().resolve(None).is_err());
+        assert!(TileConfig::default().resolve(Some((128, 64))).is_err());
+        assert!(TileConfig { size: 64, overlap: 16 }.resolve(Some((128, 128))).is_err());
+        assert!(TileConfig { size: 16, overlap: 16 }.resolve(None).is_err());
+    }
+}
@@ -0,0 +1,255 @@
+// src/ai/upscaler.rs
+
+use anyhow::Result;
+use image::imageops::FilterType;
+use crate::video::types::{RawFrame, Sample};
+use crate::ai::processor::AIProcessor;
+
+/// Which backend turns decoded frames into upscaled ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UpscalerKind {
+    /// The ONNX model at `Config::model_path`.
+    #[default]
+    Onnx,
+    Lanczos3,
+    Bicubic,
+    Nearest,
+    /// Directional cubic interpolation along local edges, in 2x steps.
+    EdgeDirected,
+}
+
+impl UpscalerKind {
+    pub fn needs_model(&self) -> bool {
+        *self == UpscalerKind::Onnx
+    }
+
+    /// The model-free backend, None for `Onnx`.
+    pub fn classic<T: Sample>(&self) -> Option<Box<dyn Upscaler<T>>> {
+        match self {
+            UpscalerKind::Onnx => None,
+            UpscalerKind::Lanczos3 => Some(Box::new(Resampler { filter: FilterType::Lanczos3 })),
+            UpscalerKind::Bicubic => Some(Box::new(Resampler { filter: FilterType::CatmullRom })),
+            UpscalerKind::Nearest => Some(Box::new(Resampler { filter: FilterType::Nearest })),
+            UpscalerKind::EdgeDirected => Some(Box::new(EdgeDirected)),
+        }
+    }
+}
+
+/// What an upscaler produced for one frame.
+pub enum Upscaled<T> {
+    /// Interleaved RGB.
+    Rgb { data: Vec<T>, width: i32, height: i32 },
+    /// Luma only; chroma is taken from a plain resize of the source frame.
+    Luma { data: Vec<T>, width: i32, height: i32 },
+}
+
+/// Turns decoded frames into larger ones. Implemented by the ONNX model and by
+/// classical resamplers, so the pipeline runs the same with or without a model.
+pub trait Upscaler<T: Sample> {
+    /// One line for the log.
+    fn describe(&self) -> String;
+
+    /// Frames `upscale` takes at once.
+    fn batch_size(&self) -> usize {
+        1
+    }
+
+    /// Upscales `frames` towards `width`x`height`, in input order. The result may come
+    /// out at another size (a model's fixed scale); the caller resizes it to fit.
+    /// Upscalers that look ahead (temporal models) may return fewer results than
+    /// frames and catch up in later calls.
+    fn upscale(&mut self, frames: &[RawFrame<T>], width: i32, height: i32) -> Result<Vec<Upscaled<T>>>;
+
+    /// Results of the frames still held back, once the input has ended.
+    fn flush(&mut self) -> Result<Vec<Upscaled<T>>> {
+        Ok(Vec::new())
+    }
+}
+
+impl<T: Sample> Upscaler<T> for AIProcessor<'_> {
+    fn describe(&self) -> String {
+        format!("model {}", self.info)
+    }
+
+    fn batch_size(&self) -> usize {
+        AIProcessor::batch_size(self)
+    }
+
+    fn upscale(&mut self, frames: &[RawFrame<T>], _width: i32, _height: i32) -> Result<Vec<Upscaled<T>>> {
+        Ok(if self.is_rgb() {
+            self.process_batch_rgb(frames)?.into_iter()
+                .map(|(data, width, height)| Upscaled::Rgb { data, width, height })
+                .collect()
+        } else {
+            self.process_batch_y(frames)?.into_iter()
+                .map(|(data, width, height)| Upscaled::Luma { data, width, height })
+                .collect()
+        })
+    }
+
+    fn flush(&mut self) -> Result<Vec<Upscaled<T>>> {
+        Ok(if self.is_rgb() {
+            self.flush_rgb()?.into_iter()
+                .map(|(data, width, height)| Upscaled::Rgb { data, width, height })
+                .collect()
+        } else {
+            self.flush_y()?.into_iter()
+                .map(|(data, width, height)| Upscaled::Luma { data, width, height })
+                .collect()
+        })
+    }
+}
+
+/// Plain resampling filter straight to the target size.
+struct Resampler {
+    filter: FilterType,
+}
+
+impl<T: Sample> Upscaler<T> for Resampler {
+    fn describe(&self) -> String {
+        format!("{:?} resampling", self.filter)
+    }
+
+    fn upscale(&mut self, frames: &[RawFrame<T>], width: i32, height: i32) -> Result<Vec<Upscaled<T>>> {
+        Ok(frames.iter().map(|frame| Upscaled::Rgb {
+            data: T::resize_rgb_with(&frame.data, frame.width as u32, frame.height as u32, width as u32, height as u32, self.filter),
+            width,
+            height,
+        }).collect())
+    }
+}
+
+/// Edge-directed interpolation after directional cubic convolution (DCCI): each 2x step
+/// fills new pixels along the direction with less variation, so edges stay sharp
+/// without the stair-stepping of separable filters. The last step overshoots and
+/// is brought to the exact size with a cubic resize.
+struct EdgeDirected;
+
+impl<T: Sample> Upscaler<T> for EdgeDirected {
+    fn describe(&self) -> String {
+        "edge-directed interpolation".to_string()
+    }
+
+    fn upscale(&mut self, frames: &[RawFrame<T>], width: i32, height: i32) -> Result<Vec<Upscaled<T>>> {
+        Ok(frames.iter().map(|frame| {
+            let (mut w, mut h) = (frame.width as usize, frame.height as usize);
+            let mut pixels: Vec<Px> = frame.data.chunks_exact(3)
+                .map(|p| [p[0].to_unit() * 255.0, p[1].to_unit() * 255.0, p[2].to_unit() * 255.0])
+                .collect();
+            while w < width as usize || h < height as usize {
+                pixels = double(&pixels, w, h);
+                w *= 2;
+                h *= 2;
+            }
+            let rgb: Vec<T> = pixels.iter().flatten().map(|&v| T::from_unit(v / 255.0)).collect();
+            let data = if (w, h) == (width as usize, height as usize) {
+                rgb
+            } else {
+                T::resize_rgb(&rgb, w as u32, h as u32, width as u32, height as u32)
+            };
+            Upscaled::Rgb { data, width, height }
+        }).collect())
+    }
+}
+
+type Px = [f32; 3];
+
+// Gradient ratio above which interpolation follows one direction only
+const EDGE_THRESHOLD: f32 = 1.15;
+// Sharpness of the blend between directions in smooth areas
+const WEIGHT_EXPONENT: i32 = 5;
+
+fn luma(p: &Px) -> f32 {
+    0.299 * p[0] + 0.587 * p[1] + 0.114 * p[2]
+}
+
+// Cubic convolution at the midpoint of four equally spaced samples
+fn cubic(p0: Px, p1: Px, p2: Px, p3: Px) -> Px {
+    std::array::from_fn(|c| (9.0 * (p1[c] + p2[c]) - p0[c] - p3[c]) / 16.0)
+}
+
+// `along_a`/`along_b` interpolate along two directions, `grad_a`/`grad_b` measure the
+// variation along each; the smoother direction wins
+fn blend(along_a: Px, along_b: Px, grad_a: f32, grad_b: f32) -> Px {
+    if (1.0 + grad_a) / (1.0 + grad_b) > EDGE_THRESHOLD {
+        return along_b;
+    }
+    if (1.0 + grad_b) / (1.0 + grad_a) > EDGE_THRESHOLD {
+        return along_a;
+    }
+    let wa = 1.0 / (1.0 + grad_a.powi(WEIGHT_EXPONENT));
+    let wb = 1.0 / (1.0 + grad_b.powi(WEIGHT_EXPONENT));
+    std::array::from_fn(|c| (wa * along_a[c] + wb * along_b[c]) / (wa + wb))
+}
+
+fn clamp(v: isize, n: usize) -> usize {
+    v.clamp(0, n as isize - 1) as usize
+}
+
+// Clamps into 0..n keeping the parity of `v`, so it lands on a pixel of the same kind
+fn clamp_parity(v: isize, n: usize) -> usize {
+    let max = n as isize - 1;
+    if v < 0 {
+        v.rem_euclid(2) as usize
+    } else if v > max {
+        (max - (max - v).rem_euclid(2)) as usize
+    } else {
+        v as usize
+    }
+}
+
+// Doubles a `w`x`h` image: source pixels land on even coordinates, then the diagonal
+// centers are interpolated from the source, then the rest from both
+fn double(src: &[Px], w: usize, h: usize) -> Vec<Px> {
+    let (dw, dh) = (w * 2, h * 2);
+    let mut dst = vec![[0.0; 3]; dw * dh];
+    for y in 0..h {
+        for x in 0..w {
+            dst[2 * y * dw + 2 * x] = src[y * w + x];
+        }
+    }
+
+    let lo = |x: isize, y: isize| src[clamp(y, h) * w + clamp(x, w)];
+    for y in 0..h as isize {
+        for x in 0..w as isize {
+            // Variation along 45 (up-right) and 135 (down-right) in the 4x4 neighborhood
+            let (mut g45, mut g135) = (0.0, 0.0);
+            for b in y - 1..=y + 2 {
+                for a in x - 1..x + 2 {
+                    if b > y - 1 {
+                        g45 += (luma(&lo(a + 1, b - 1)) - luma(&lo(a, b))).abs();
+                    }
+                    if b < y + 2 {
+                        g135 += (luma(&lo(a + 1, b + 1)) - luma(&lo(a, b))).abs();
+                    }
+                }
+            }
+            let p45 = cubic(lo(x - 1, y + 2), lo(x, y + 1), lo(x + 1, y), lo(x + 2, y - 1));
+            let p135 = cubic(lo(x - 1, y - 1), lo(x, y), lo(x + 1, y + 1), lo(x + 2, y + 2));
+            dst[(2 * y as usize + 1) * dw + 2 * x as usize + 1] = blend(p45, p135, g45, g135);
+        }
+    }
+
+    // Pixels with one odd coordinate: their horizontal and vertical neighbors at odd
+    // distances have both coordinates of equal parity, so they are filled by now and
+    // never written below; clamping keeps that parity at the edges
+    let hi = |dst: &[Px], x: isize, y: isize| dst[clamp_parity(y, dh) * dw + clamp_parity(x, dw)];
+    for y in 0..dh as isize {
+        for x in 0..dw as isize {
+            if (x + y) % 2 == 0 {
+                continue;
+            }
+            let (mut gh, mut gv) = (0.0, 0.0);
+            for d in [-2, 0, 2] {
+                for a in [-3, -1, 1] {
+                    gh += (luma(&hi(&dst, x + a + 2, y + d)) - luma(&hi(&dst, x + a, y + d))).abs();
+                    gv += (luma(&hi(&dst, x + d, y + a + 2)) - luma(&hi(&dst, x + d, y + a))).abs();
+                }
+            }
+            let ph = cubic(hi(&dst, x - 3, y), hi(&dst, x - 1, y), hi(&dst, x + 1, y), hi(&dst, x + 3, y));
+            let pv = cubic(hi(&dst, x, y - 3), hi(&dst, x, y - 1), hi(&dst, x, y + 1), hi(&dst, x, y + 3));
+            dst[y as usize * dw + x as usize] = blend(ph, pv, gh, gv);
+        }
+    }
+    dst
+}
@@ -1,83 +1,215 @@
 // src/api.rs
 
+use std::collections::VecDeque;
 use std::sync::Arc;
 use tokio::task;
 use crossbeam_channel::bounded;
 use onnxruntime::environment::Environment;
-use anyhow::{Result, anyhow};
+use anyhow::anyhow;
 use crate::video::decoder;
-use crate::video::encoder::{self, EncoderConfig};
-use crate::video::types::{DecoderMsg, EncoderMsg};
-use crate::ai::processor::{AIProcessor, upscale_grayscale, upscale_to_original, rgb_to_yuv420p, save_ppm};
+use crate::video::encoder::{self, EncoderConfig, EncoderOptions};
+use crate::video::audio::AudioOptions;
+use crate::video::streams::{StreamSelection, find_video_stream};
+use crate::video::types::{DecoderMsg, EncoderMsg, RawFrame, UpscaledFrame, BitDepth};
+use crate::video::color::ColorInfo;
+use crate::video::wrappers::{codecpar, pix_fmt_depth, SafeFormatContextInput};
+use crate::video::scale::{ScaleMode, ScalePlan};
+use crate::video::stabilize::Stabilizer;
+use crate::video::scene::{self, SceneDetection, SceneDetector};
+use crate::error::{XStreamError, FirstError};
+use crate::progress::{self, ProgressCallback, ProgressCounters, ProgressEvent};
+use crate::cancel::CancelToken;
+use crate::processing::{BuildChain, ChainBuilder, ChainSample, FrameProcessor, LumaDump, ProcessorChain, ProcessorContext, UpscaleStage};
+use crate::ai::pool;
+use crate::ai::session::SessionOptions;
+use crate::ai::model::ModelInfo;
+use crate::ai::processor::AIProcessor;
+use crate::ai::upscaler::{Upscaler, UpscalerKind};
+use crate::ai::tiling::TileConfig;
+use crate::ai::normalize::NormalizationMode;
 use rsmpeg::ffi;
-use std::ffi::CString;
-use std::ptr;
+use std::thread::JoinHandle;
 
 pub struct Config {
     pub input_path: String,
     pub output_path: String,
-    pub model_path: String,
-    pub target_resolution: (u32, u32), // e.g. (1920, 1080)
+    pub model_path: String,       // only read by the ONNX upscaler
+    pub upscaler: UpscalerKind,   // ONNX model or a classical resampler
+    pub scale: ScaleMode, // output size and how the source aspect ratio is kept
+    pub tiling: Option<TileConfig>, // None = whole frame, tiled anyway if a fixed model input is smaller
+    pub normalization: NormalizationMode,
+    pub encoder: EncoderOptions, // codec, container, rate control and private options
+    pub audio: AudioOptions,     // copy or re-encode audio tracks
+    pub streams: StreamSelection, // which non-video streams are carried over
+    pub bit_depth: BitDepth,
+    pub batch_size: usize, // frames (or tiles) per inference, clamped to a fixed model batch
+    pub workers: Option<usize>, // parallel AI sessions, None = physical cores / intra-op threads
+    pub stabilization: f32, // 0-1 blend with the motion-compensated previous frame against flicker, 0 = off
+    pub scene_detection: Option<SceneDetection>, // keyframes and temporal resets at cuts, None = off
+    pub debug_luma: Option<String>, // grayscale PPM of the first output frame's luma, None = off
+    pub session: SessionOptions, // threads and graph optimization per session
 }
 
 pub struct Engine {
     config: Config,
     env: Arc<Environment>,
+    progress: Option<ProgressCallback>,
+    cancel: CancelToken,
+    processors: Option<Arc<dyn ChainBuilder>>,
 }
 
 impl Engine {
-    pub fn new(config: Config) -> Result<Self> {
+    pub fn new(config: Config) -> Result<Self, XStreamError> {
         let env = Arc::new(Environment::builder()
             .with_name("x_stream_env")
             .with_log_level(onnxruntime::LoggingLevel::Warning)
-            .build()?);
-        Ok(Self { config, env })
+            .build()
+            .map_err(|e| XStreamError::Inference(e.into()))?);
+        Ok(Self { config, env, progress: None, cancel: CancelToken::default(), processors: None })
     }
 
-    pub async fn run(&self) -> Result<()> {
+    /// Token that stops `run` early; `run` then finalizes the output and returns
+    /// `XStreamError::Cancelled`.
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    /// Calls `callback` with frame counts, rate, ETA and output size while `run` works,
+    /// from a reporting thread. To consume the events elsewhere, send them into a channel.
+    pub fn with_progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&ProgressEvent) + Send + Sync + 'static,
+    {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Adds custom processors around the upscaler (or in place of it) on every worker.
+    /// `builder` is called once per worker, so each gets its own instances.
+    pub fn with_processors<B: BuildChain + 'static>(mut self, builder: B) -> Self {
+        self.processors = Some(Arc::new(builder));
+        self
+    }
+
+    pub async fn run(&self) -> Result<(), XStreamError> {
         println!("🚀 X-Stream Engine Starting...");
         println!("📂 Input: {}", self.config.input_path);
         println!("📂 Output: {}", self.config.output_path);
 
+        // --- GET METADATA FOR ENCODER SETUP ---
+        let (width, height, sar, time_base, frame_rate, source_depth, color, total_frames) = {
+            let in_ctx = SafeFormatContextInput::open(&self.config.input_path).map_err(XStreamError::Probe)?;
+            let video_stream_idx = find_video_stream(&in_ctx)
+                .ok_or_else(|| XStreamError::Probe(anyhow!("No video stream in {}", self.config.input_path)))?;
+            let s = in_ctx.stream(video_stream_idx)
+                .ok_or_else(|| XStreamError::Probe(anyhow!("No video stream in {}", self.config.input_path)))?;
+            let par = codecpar(s);
+            let sar = in_ctx.sample_aspect_ratio(video_stream_idx);
+            let mut fr = s.avg_frame_rate; // Avg fps
+            if fr.num <= 0 || fr.den <= 0 {
+                fr = s.r_frame_rate;
+            }
+            let depth = pix_fmt_depth(par.format).unwrap_or(8);
+            let color = ColorInfo::from_codecpar(par).resolve(par.height);
+            let duration_secs = if s.duration != ffi::AV_NOPTS_VALUE && s.duration > 0 {
+                Some(s.duration as f64 * s.time_base.num as f64 / s.time_base.den as f64)
+            } else if in_ctx.duration != ffi::AV_NOPTS_VALUE && in_ctx.duration > 0 {
+                Some(in_ctx.duration as f64 / ffi::AV_TIME_BASE as f64)
+            } else {
+                None
+            };
+            let fps = if fr.den > 0 { fr.num as f64 / fr.den as f64 } else { 0.0 };
+            let total_frames = progress::estimate_total_frames(s.nb_frames, duration_secs, fps);
+            (par.width, par.height, sar, s.time_base, fr, depth, color, total_frames)
+        };
+
+        let plan = self.config.scale.plan(width as u32, height as u32, sar).map_err(XStreamError::Probe)?;
+        println!("📐 {}x{} -> {}x{} ({:?})", width, height, plan.output.0, plan.output.1, self.config.scale);
+
+        if color.is_hdr() {
+            println!("🌈 HDR source (transfer {}), color metadata is passed through", color.transfer);
+        }
+
+        let high_bit_depth = match self.config.bit_depth {
+            BitDepth::Auto => source_depth > 8,
+            BitDepth::Eight => false,
+            BitDepth::Sixteen => true,
+        };
+        if high_bit_depth {
+            println!("🎨 16-bit pipeline ({}-bit source)", source_depth);
+            self.run_pipeline::<u16>(plan, time_base, frame_rate, color, total_frames).await?;
+        } else {
+            self.run_pipeline::<u8>(plan, time_base, frame_rate, color, total_frames).await?;
+        }
+
+        if self.cancel.is_cancelled() {
+            println!("\n🛑 Cancelled, {} finalized with the frames done so far", self.config.output_path);
+            return Err(XStreamError::Cancelled);
+        }
+        println!("\n✨ Engine Finished Successfully.");
+        Ok(())
+    }
+
+    async fn run_pipeline<T: ChainSample>(
+        &self,
+        plan: ScalePlan,
+        time_base: ffi::AVRational,
+        frame_rate: ffi::AVRational,
+        color: ColorInfo,
+        total_frames: Option<u64>,
+    ) -> Result<(), XStreamError> {
         let input = self.config.input_path.clone();
         let output = self.config.output_path.clone();
         let model = self.config.model_path.clone();
         let env = self.env.clone();
-        let (tw, th) = self.config.target_resolution;
-
-        // --- GET METADATA FOR ENCODER SETUP ---
-        let (width, height, time_base) = unsafe {
-            let in_c = CString::new(input.clone()).unwrap();
-            let mut in_ctx = ptr::null_mut();
-            if ffi::avformat_open_input(&mut in_ctx, in_c.as_ptr(), ptr::null_mut(), ptr::null_mut()) < 0 {
-                return Err(anyhow!("Failed to probe input config"));
+        let (tw, th) = plan.output;
+        let mut tiling = self.config.tiling;
+        let normalization = self.config.normalization;
+        let batch_size = self.config.batch_size.max(1);
+        let session_options = self.config.session.clone();
+        let mut workers = self.config.workers.unwrap_or_else(|| pool::default_workers(session_options.intra_threads)).max(1);
+        let upscaler_kind = self.config.upscaler;
+        if upscaler_kind.needs_model() {
+            let info = {
+                let mut session = session_options.build(&env, &model).map_err(XStreamError::Inference)?;
+                ModelInfo::from_session(&mut session).map_err(XStreamError::Inference)?
+            };
+            // A temporal model needs every frame's neighbors, which only one worker sees
+            if let Some(frames) = info.frames.filter(|_| workers > 1) {
+                println!("🎞️ Temporal model ({}-frame window), running a single worker", frames);
+                workers = 1;
             }
-            ffi::avformat_find_stream_info(in_ctx, ptr::null_mut());
-             let mut video_stream_idx = -1;
-            for i in 0..(*in_ctx).nb_streams {
-                // Fixed dereference error here
-                if (*(*(*(*in_ctx).streams.add(i as usize))).codecpar).codec_type == ffi::AVMEDIA_TYPE_VIDEO {
-                    video_stream_idx = i as i32;
-                    break;
+            // A fixed-size model would otherwise see the whole frame shrunk to its input
+            if let (None, Some((mw, mh))) = (tiling, info.input_size) {
+                let (cw, ch) = (plan.crop.width, plan.crop.height);
+                if cw > mw || ch > mh {
+                    match TileConfig::default().resolve(info.input_size) {
+                        Ok(tile) if info.frames.is_none() => {
+                            println!("🧩 Model input is {}x{}, smaller than the {}x{} frame, upscaling in tiles", mw, mh, cw, ch);
+                            tiling = Some(tile);
+                        }
+                        _ => eprintln!("⚠️ Model input is {}x{}, frames of {}x{} are downscaled to it before upscaling", mw, mh, cw, ch),
+                    }
                 }
             }
-            if video_stream_idx == -1 { return Err(anyhow!("No video stream")); }
-            let s = *(*in_ctx).streams.add(video_stream_idx as usize);
-            let w = (*(*s).codecpar).width;
-            let h = (*(*s).codecpar).height;
-            let tb = (*s).avg_frame_rate; // Avg fps
-            ffi::avformat_close_input(&mut in_ctx);
-            (w, h, ffi::av_inv_q(tb)) 
-        };
+        }
+        let subsampling = self.config.encoder.chroma_subsampling();
         
-        let (tx_video_raw, rx_video_raw) = bounded::<DecoderMsg>(5); 
-        let (tx_encoder, rx_encoder) = bounded::<EncoderMsg>(5);
-        let tx_encoder_audio = tx_encoder.clone();
+
+        let (tx_video_raw, rx_video_raw) = bounded::<DecoderMsg<T>>(5); 
+        let (tx_encoder, rx_encoder) = bounded::<EncoderMsg<T>>(5);
+        let tx_encoder_packets = tx_encoder.clone();
+        // Any stage that fails records its error here and the others wind down
+        let errors = FirstError::default();
+        let counters = ProgressCounters::default();
+        let mut stages = Stages::new(&errors, workers + 5);
 
         // --- THREAD 1: DECODER ---
         let input_dec = input.clone();
-        let decoder_handle = std::thread::spawn(move || {
-            decoder::run_decoder(&input_dec, tx_video_raw, tx_encoder_audio)
+        let dec_counters = counters.clone();
+        let dec_cancel = self.cancel.clone();
+        stages.spawn("decoder", XStreamError::Decode, move || {
+            decoder::run_decoder(&input_dec, color, tx_video_raw, tx_encoder_packets, dec_counters, dec_cancel)
         });
 
         // --- THREAD 2: ENCODER ---
@@ -87,63 +219,247 @@ impl Engine {
             width: tw as i32,
             height: th as i32,
             time_base: time_base,
-            bitrate: 4_000_000,
+            frame_rate,
+            color,
+            sample_aspect_ratio: plan.sample_aspect_ratio,
+            audio: self.config.audio.clone(),
+            streams: self.config.streams.clone(),
+            options: self.config.encoder.clone(),
         };
-        let encoder_handle = std::thread::spawn(move || {
-            encoder::run_encoder(&input_enc, &output_enc, rx_encoder, enc_config)
+        let enc_counters = counters.clone();
+        stages.spawn("encoder", XStreamError::Encode, move || {
+            encoder::run_encoder(&input_enc, &output_enc, rx_encoder, enc_config, enc_counters)
         });
 
-        // --- THREAD 3: AI ---
-        let ai_model = model.clone();
-        let ai_env = env.clone();
-        let ai_handle = std::thread::spawn(move || {
-             let mut ai = AIProcessor::new(&ai_model, &ai_env).expect("Failed to init AI");
-             
-             for msg in rx_video_raw {
-                match msg {
-                    DecoderMsg::Video(raw) => {
-                         if let Ok(ai_y_pixels) = ai.process_frame_y(&raw) {
-                             let ai_y_upscaled = upscale_grayscale(&ai_y_pixels, 224, 224, tw as i32, th as i32);
-                             let raw_upscaled_rgb = upscale_to_original(&raw.data, raw.width, raw.height, tw as i32, th as i32);
-                             let mut yuv_data = rgb_to_yuv420p(&raw_upscaled_rgb, tw as i32, th as i32);
-                             let y_size = (tw * th) as usize;
-                             if ai_y_upscaled.len() == y_size && yuv_data.len() >= y_size {
-                                 yuv_data[0..y_size].copy_from_slice(&ai_y_upscaled);
-                             }
-
-                             if raw.pts == 0 {
-                                 let mut debug_rgb = Vec::with_capacity((tw * th * 3) as usize);
-                                 for &y_pixel in &yuv_data[0..y_size] {
-                                     debug_rgb.push(y_pixel);
-                                     debug_rgb.push(y_pixel);
-                                     debug_rgb.push(y_pixel);
-                                 }
-                                 let _ = save_ppm("debug_luma.ppm", &debug_rgb, tw as i32, th as i32);
-                             }
-
-                             let up_frame = crate::video::types::UpscaledFrame {
-                                 data: yuv_data,
-                                 width: tw as i32,
-                                 height: th as i32,
-                                 pts: raw.pts,
-                             };
-                             tx_encoder.send(EncoderMsg::Video(up_frame)).unwrap();
-                         }
-                    },
-                    DecoderMsg::Audio(_) => {},
-                    DecoderMsg::EOF => break,
+        // --- THREADS 3..N: AI WORKERS ---
+        // Frames are numbered in decode order, spread over the workers and put back
+        // in order before the encoder
+        let (tx_frames, rx_frames) = bounded::<(u64, RawFrame<T>)>(workers * batch_size);
+        let (tx_done, rx_done) = bounded::<(u64, UpscaledFrame<T>)>(workers * batch_size);
+        let seq_errors = errors.clone();
+        let scene_detection = self.config.scene_detection.clone();
+        stages.spawn("sequencer", XStreamError::Decode, move || {
+            let mut detector = scene_detection.as_ref().map(|s| SceneDetector::new(s.threshold));
+            let frames = pool::run_sequencer(rx_video_raw, tx_frames, &seq_errors, detector.as_mut());
+            if let (Some(detector), Some(settings)) = (&detector, &scene_detection) {
+                println!("\n🎬 {} scene cut(s) in {} frames", detector.cuts().len(), frames);
+                if let Some(path) = &settings.sidecar {
+                    scene::write_sidecar(path, detector.cuts(), frames, frame_rate).map_err(XStreamError::Io)?;
+                    println!("📝 Scene list: {}", path);
                 }
-             }
-             tx_encoder.send(EncoderMsg::EOF).unwrap();
+            }
+            Ok(())
+        });
+        // Steps that need every frame in order run after the reorder
+        let mut ordered: Vec<Box<dyn FrameProcessor<UpscaledFrame<T>> + Send>> = Vec::new();
+        if self.config.stabilization > 0.0 {
+            ordered.push(Box::new(Stabilizer::new(self.config.stabilization)));
+        }
+        if let Some(path) = &self.config.debug_luma {
+            ordered.push(Box::new(LumaDump::new(path)));
+        }
+        let tx_ordered = if ordered.is_empty() {
+            tx_encoder
+        } else {
+            println!("🎚️ {}", ordered.name());
+            let (tx_ordered, rx_ordered) = bounded::<EncoderMsg<T>>(5);
+            let ordered_errors = errors.clone();
+            stages.spawn("ordered", XStreamError::Encode, move || {
+                pool::run_ordered(ordered, rx_ordered, tx_encoder, &ordered_errors)
+            });
+            tx_ordered
+        };
+        let reorder_errors = errors.clone();
+        stages.spawn("reorder", XStreamError::Encode, move || {
+            pool::run_reorder(rx_done, tx_ordered, &reorder_errors);
+            Ok(())
         });
 
+        for worker in 0..workers {
+            let ai_model = model.clone();
+            let ai_env = env.clone();
+            let rx_frames = rx_frames.clone();
+            let tx_done = tx_done.clone();
+            let session_options = session_options.clone();
+            let worker_errors = errors.clone();
+            let worker_counters = counters.clone();
+            let custom = self.processors.clone();
+            stages.spawn(&format!("ai-{}", worker), XStreamError::Inference, move || {
+             let ai: Box<dyn Upscaler<T>> = match upscaler_kind.classic() {
+                 Some(classic) => classic,
+                 None => {
+                     let mut model = AIProcessor::new(&ai_model, &ai_env, &session_options)?
+                         .with_normalization(normalization)
+                         .with_color(&color)
+                         .with_batch_size(batch_size);
+                     if let Some(tile) = tiling {
+                         model = model.with_tiling(tile)?;
+                     }
+                     Box::new(model)
+                 }
+             };
+             let ctx = ProcessorContext { plan, color, subsampling, worker, workers };
+             let mut chain = ProcessorChain::new(UpscaleStage::new(ai));
+             if let Some(custom) = &custom {
+                 T::build_chain(custom.as_ref(), &ctx, &mut chain)?;
+             }
+             chain.init(&ctx)?;
+             if worker == 0 {
+                 println!("🧠 Processing: {} ({} worker(s))", chain.name(), workers);
+             }
+
+             // Sequence numbers of frames in the chain, in order; processors that hold
+             // frames back release them later, but never out of order
+             let mut pending = VecDeque::new();
+             let send = |frames: Vec<UpscaledFrame<T>>, pending: &mut VecDeque<u64>| -> anyhow::Result<bool> {
+                 for up_frame in frames {
+                     let seq = pending.pop_front().ok_or_else(|| anyhow!("Processor chain returned more frames than it was given"))?;
+                     if tx_done.send((seq, up_frame)).is_err() {
+                         return Ok(false); // Downstream stopped, its own error (if any) is recorded
+                     }
+                     worker_counters.add_inferred();
+                 }
+                 Ok(true)
+             };
+
+             // Each worker collects a full batch (or what is left at the end) and processes it
+             let batch_size = chain.batch_size().max(1);
+             let mut open = true;
+             while open && !worker_errors.is_set() {
+                let mut batch = Vec::with_capacity(batch_size);
+                while batch.len() < batch_size {
+                    match rx_frames.recv() {
+                        Ok((seq, raw)) => {
+                            pending.push_back(seq);
+                            batch.push(plan.crop_frame(raw));
+                        }
+                        Err(_) => {
+                            open = false;
+                            break;
+                        }
+                    }
+                }
+                if batch.is_empty() {
+                    break;
+                }
+                if !send(chain.process(batch)?, &mut pending)? {
+                    return Ok(());
+                }
+             }
+             if worker_errors.is_set() {
+                 return Ok(());
+             }
+             if !send(chain.flush()?, &mut pending)? {
+                 return Ok(());
+             }
+             if !pending.is_empty() {
+                 return Err(anyhow!("Processor chain kept {} frame(s) after the flush", pending.len()));
+             }
+             Ok(())
+            });
+        }
+        // Only the workers hold these now, so the stages below see them close
+        drop(rx_frames);
+        drop(tx_done);
+
+        // Reports until every stage has finished, see below
+        let (stop_progress, progress_stopped) = bounded::<()>(0);
+        let reporter = match &self.progress {
+            Some(callback) => {
+                let callback = callback.clone();
+                let spawned = std::thread::Builder::new().name("progress".to_string()).spawn(move || {
+                    progress::run_reporter(counters, total_frames, callback, progress_stopped)
+                });
+                match spawned {
+                    Ok(reporter) => Some(reporter),
+                    Err(err) => {
+                        errors.record(XStreamError::Io(err));
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+
+        // Every stage catches its own errors and panics, so joining cannot fail
         task::spawn_blocking(move || {
-            decoder_handle.join().unwrap().unwrap();
-            ai_handle.join().unwrap();
-            encoder_handle.join().unwrap().unwrap();
-        }).await?;
+            stages.join();
+            drop(stop_progress);
+            if let Some(reporter) = reporter {
+                let _ = reporter.join();
+            }
+        }).await.map_err(|e| XStreamError::Io(std::io::Error::other(e)))?;
 
-        println!("\n✨ Engine Finished Successfully.");
-        Ok(())
+        match errors.take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+// Threads of the running pipeline. If one fails to start, its error is recorded and
+// the stages after it are not started: their closures are dropped along with the
+// channel ends they own, so the stages already running see their peers close and
+// wind down before `join` returns.
+struct Stages {
+    handles: Vec<JoinHandle<()>>,
+    errors: FirstError,
+    failed: bool,
+}
+
+impl Stages {
+    fn new(errors: &FirstError, capacity: usize) -> Self {
+        Self { handles: Vec::with_capacity(capacity), errors: errors.clone(), failed: false }
     }
+
+    fn spawn<F>(&mut self, name: &str, kind: fn(anyhow::Error) -> XStreamError, stage: F)
+    where
+        F: FnOnce() -> anyhow::Result<()> + Send + 'static,
+    {
+        if self.failed {
+            return;
+        }
+        match spawn_stage(name, &self.errors, kind, stage) {
+            Ok(handle) => self.handles.push(handle),
+            Err(err) => {
+                self.errors.record(err);
+                self.failed = true;
+            }
+        }
+    }
+
+    fn join(self) {
+        for handle in self.handles {
+            let _ = handle.join();
+        }
+    }
+}
+
+// Runs one pipeline stage on a named thread. An error the stage returns is filed
+// under `kind` unless it is already an `XStreamError`; a panic is turned into an
+// error too. Either way it is recorded in `errors` so the other stages stop.
+fn spawn_stage<F>(
+    name: &str,
+    errors: &FirstError,
+    kind: fn(anyhow::Error) -> XStreamError,
+    stage: F,
+) -> Result<JoinHandle<()>, XStreamError>
+where
+    F: FnOnce() -> anyhow::Result<()> + Send + 'static,
+{
+    let errors = errors.clone();
+    let thread_name = name.to_string();
+    let handle = std::thread::Builder::new().name(name.to_string()).spawn(move || {
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(stage)) {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => errors.record(XStreamError::classify(err, kind)),
+            Err(panic) => {
+                let msg = panic.downcast_ref::<&str>().map(|s| s.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown panic".to_string());
+                errors.record(kind(anyhow!("{} thread panicked: {}", thread_name, msg)));
+            }
+        }
+    })?;
+    Ok(handle)
 }
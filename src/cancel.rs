@@ -0,0 +1,23 @@
+// src/cancel.rs
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Asks a running `Engine::run` to stop early. The decoder stops reading and drains
+/// the frames it still buffers; those and the frames already in flight are upscaled
+/// and encoded, and the output is finalized so it stays playable. Clones share the
+/// same flag; safe to use from a signal handler.
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
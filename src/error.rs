@@ -0,0 +1,102 @@
+// src/error.rs
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Why a run failed, by pipeline stage. Each variant keeps the underlying
+/// error with its context chain as `source`.
+#[derive(Debug)]
+pub enum XStreamError {
+    /// Reading the input's streams and parameters before the pipeline starts.
+    Probe(anyhow::Error),
+    /// Demuxing or decoding the input.
+    Decode(anyhow::Error),
+    /// Loading the model or running it on frames.
+    Inference(anyhow::Error),
+    /// Encoding video or audio.
+    Encode(anyhow::Error),
+    /// Writing the output container.
+    Mux(anyhow::Error),
+    /// Files, pipes and threads.
+    Io(std::io::Error),
+    /// Stopped through a `CancelToken`; the output was finalized with the frames done so far.
+    Cancelled,
+}
+
+impl XStreamError {
+    /// Keeps an error a stage already classified, otherwise files it under `kind`.
+    pub fn classify(err: anyhow::Error, kind: fn(anyhow::Error) -> XStreamError) -> XStreamError {
+        match err.downcast::<XStreamError>() {
+            Ok(err) => err,
+            Err(err) => kind(err),
+        }
+    }
+
+    /// An FFmpeg I/O failure (`AVERROR(errno)`) as an `Io` error naming `path`.
+    pub fn io(ret: i32, path: &str) -> XStreamError {
+        let err = std::io::Error::from_raw_os_error(-ret);
+        XStreamError::Io(std::io::Error::new(err.kind(), format!("{}: {}", path, err)))
+    }
+}
+
+impl fmt::Display for XStreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XStreamError::Probe(e) => write!(f, "probe failed: {}", e),
+            XStreamError::Decode(e) => write!(f, "decode failed: {}", e),
+            XStreamError::Inference(e) => write!(f, "inference failed: {}", e),
+            XStreamError::Encode(e) => write!(f, "encode failed: {}", e),
+            XStreamError::Mux(e) => write!(f, "mux failed: {}", e),
+            XStreamError::Io(e) => write!(f, "I/O error: {}", e),
+            XStreamError::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for XStreamError {
+    // The message above already shows the outermost error, so the chain starts below it
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            XStreamError::Probe(e)
+            | XStreamError::Decode(e)
+            | XStreamError::Inference(e)
+            | XStreamError::Encode(e)
+            | XStreamError::Mux(e) => e.source(),
+            XStreamError::Io(e) => e.source(),
+            XStreamError::Cancelled => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for XStreamError {
+    fn from(err: std::io::Error) -> Self {
+        XStreamError::Io(err)
+    }
+}
+
+/// The first error raised by any pipeline thread. Stages poll `is_set` to wind
+/// down early; later errors are dropped as they are usually fallout of the first.
+#[derive(Clone, Default)]
+pub struct FirstError {
+    set: Arc<AtomicBool>,
+    error: Arc<Mutex<Option<XStreamError>>>,
+}
+
+impl FirstError {
+    pub fn record(&self, err: XStreamError) {
+        let mut slot = self.error.lock().unwrap_or_else(|e| e.into_inner());
+        if slot.is_none() {
+            *slot = Some(err);
+        }
+        self.set.store(true, Ordering::Release);
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.set.load(Ordering::Acquire)
+    }
+
+    pub fn take(&self) -> Option<XStreamError> {
+        self.error.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+}
@@ -2,5 +2,13 @@
 pub mod video;
 pub mod ai;
 pub mod api;
+pub mod error;
+pub mod progress;
+pub mod cancel;
+pub mod processing;
 
-pub use api::{Engine, Config};
\ No newline at end of file
+pub use api::{Engine, Config};
+pub use error::XStreamError;
+pub use progress::ProgressEvent;
+pub use cancel::CancelToken;
+pub use processing::{BuildChain, FrameProcessor, LumaDump, ProcessorChain, ProcessorContext};
\ No newline at end of file
@@ -1,6 +1,18 @@
-use clap::Parser;
-use x_stream::{Engine, Config};
-use anyhow::Result;
+use clap::{Parser, ValueEnum};
+use x_stream::{Engine, Config, ProgressEvent, XStreamError};
+use x_stream::ai::tiling::TileConfig;
+use x_stream::ai::normalize::NormalizationMode;
+use x_stream::ai::upscaler::UpscalerKind;
+use x_stream::ai::session::{OptimizationLevel, SessionOptions};
+use x_stream::video::encoder::{EncoderOptions, OutputPixelFormat, RateControl, VideoCodec};
+use x_stream::video::types::BitDepth;
+use x_stream::video::scale::ScaleMode;
+use x_stream::video::audio::{AudioCodec, AudioMode, AudioOptions};
+use x_stream::video::streams::StreamSelection;
+use x_stream::video::scene::SceneDetection;
+use anyhow::{Result, anyhow};
+use std::io::Write;
+use std::time::Duration;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -12,6 +24,456 @@ struct Args {
     /// Path to output video file
     #[arg(short, long, default_value = "output_refactored.mp4")]
     output: String,
+
+    /// Path to the ONNX model used by --upscaler onnx
+    #[arg(short, long, default_value = "model.onnx")]
+    model: String,
+
+    /// Upscaling backend: the ONNX model or a model-free resampler
+    #[arg(long, value_enum, default_value_t = UpscalerChoice::Onnx)]
+    upscaler: UpscalerChoice,
+
+    /// How the source is mapped onto the output size
+    #[arg(long, value_enum, default_value_t = Scale::Fit)]
+    scale: Scale,
+
+    /// Output width (stretch, fit, fill, width)
+    #[arg(long, default_value_t = 1920)]
+    width: u32,
+
+    /// Output height (stretch, fit, fill)
+    #[arg(long, default_value_t = 1080)]
+    height: u32,
+
+    /// Upscale factor for --scale factor
+    #[arg(long, default_value_t = 2)]
+    factor: u32,
+
+    /// Run the model on overlapping tiles at full resolution
+    #[arg(long)]
+    tiled: bool,
+
+    /// Tile size in pixels (0 = model's native input size)
+    #[arg(long, default_value_t = 0)]
+    tile_size: u32,
+
+    /// Overlap between neighboring tiles in pixels
+    #[arg(long, default_value_t = 16)]
+    tile_overlap: u32,
+
+    /// How frames are scaled into the model and its output mapped back to pixels
+    #[arg(long, value_enum, default_value_t = Normalization::Clamp01)]
+    normalization: Normalization,
+
+    /// History weight for minmax-smooth (0 = no smoothing)
+    #[arg(long, default_value_t = 0.9)]
+    norm_smoothing: f32,
+
+    /// Mean for meanstd normalization (applied to every channel)
+    #[arg(long, default_value_t = 0.5)]
+    norm_mean: f32,
+
+    /// Standard deviation for meanstd normalization (applied to every channel)
+    #[arg(long, default_value_t = 0.5)]
+    norm_std: f32,
+
+    /// Output video codec
+    #[arg(long, value_enum, default_value_t = Codec::H264)]
+    codec: Codec,
+
+    /// Output container (FFmpeg muxer name, e.g. mp4, matroska, webm, mov); guessed from the extension if omitted
+    #[arg(long)]
+    container: Option<String>,
+
+    /// Rate control mode
+    #[arg(long, value_enum, default_value_t = RateMode::Cbr)]
+    rate_control: RateMode,
+
+    /// Target bitrate in bits/s for cbr and abr
+    #[arg(long, default_value_t = 4_000_000)]
+    bitrate: i64,
+
+    /// CRF or QP value for crf and qp rate control (QP must be a whole number)
+    #[arg(long, default_value_t = 23.0)]
+    quality: f32,
+
+    /// Encoder preset (e.g. medium, slow; numeric for SVT-AV1)
+    #[arg(long)]
+    preset: Option<String>,
+
+    /// Encoder tune (e.g. film, grain)
+    #[arg(long)]
+    tune: Option<String>,
+
+    /// Codec profile (e.g. high, main10, hq)
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Codec level (e.g. 4.1)
+    #[arg(long)]
+    level: Option<String>,
+
+    /// Keyframe interval in frames
+    #[arg(long)]
+    gop: Option<i32>,
+
+    /// Maximum consecutive B-frames
+    #[arg(long)]
+    bframes: Option<i32>,
+
+    /// Extra encoder private option as key=value (repeatable)
+    #[arg(long = "encoder-opt", value_parser = parse_key_value)]
+    encoder_opts: Vec<(String, String)>,
+
+    /// Copy the audio track, re-encode it, or decide from what the container accepts
+    #[arg(long, value_enum, default_value_t = AudioHandling::Auto)]
+    audio: AudioHandling,
+
+    /// Codec for re-encoded audio
+    #[arg(long, value_enum, default_value_t = AudioTarget::Aac)]
+    audio_codec: AudioTarget,
+
+    /// Sample rate for re-encoded audio (default: source rate)
+    #[arg(long)]
+    audio_rate: Option<i32>,
+
+    /// Channel count for re-encoded audio (default: source layout)
+    #[arg(long)]
+    audio_channels: Option<i32>,
+
+    /// Bitrate in bits/s for re-encoded audio (default: encoder default)
+    #[arg(long)]
+    audio_bitrate: Option<i64>,
+
+    /// Drop all audio streams
+    #[arg(long)]
+    no_audio: bool,
+
+    /// Drop all subtitle streams
+    #[arg(long)]
+    no_subtitles: bool,
+
+    /// Drop data streams (timecode, metadata tracks)
+    #[arg(long)]
+    no_data: bool,
+
+    /// Drop attachments (fonts in Matroska)
+    #[arg(long)]
+    no_attachments: bool,
+
+    /// Drop cover art and any video stream besides the one being upscaled
+    #[arg(long)]
+    no_extra_video: bool,
+
+    /// Keep only streams with these language tags (repeatable); untagged streams are kept
+    #[arg(long = "language")]
+    languages: Vec<String>,
+
+    /// Keep only these input stream indices (repeatable)
+    #[arg(long = "map")]
+    include_streams: Vec<usize>,
+
+    /// Drop these input stream indices (repeatable)
+    #[arg(long = "drop-stream")]
+    exclude_streams: Vec<usize>,
+
+    /// Sample depth between decoder and encoder (auto = 16-bit for >8-bit sources)
+    #[arg(long, value_enum, default_value_t = Depth::Auto)]
+    bit_depth: Depth,
+
+    /// Encoder pixel format; defaults to 10-bit 4:2:0 for a 16-bit pipeline, 8-bit 4:2:0 otherwise
+    #[arg(long, value_enum)]
+    pix_fmt: Option<PixFmt>,
+
+    /// Frames (or tiles) per inference; fixed-batch models cap this at their batch size
+    #[arg(long, default_value_t = 1)]
+    batch_size: usize,
+
+    /// Parallel AI workers, each with its own model session (default: physical cores / intra-op threads)
+    #[arg(long)]
+    workers: Option<usize>,
+
+    /// Temporal stabilization strength (0-1) against flicker between frames, 0 = off
+    #[arg(long, default_value_t = 0.0)]
+    stabilize: f32,
+
+    /// Detect scene cuts with this score threshold (0-1, default 0.35) to force keyframes
+    /// and reset temporal state there
+    #[arg(long)]
+    scene_threshold: Option<f32>,
+
+    /// Write the detected scene cuts to this file (EDL for .edl, JSON otherwise); enables detection
+    #[arg(long)]
+    scene_list: Option<String>,
+
+    /// Write the luma of the first output frame to this PPM file, for debugging
+    #[arg(long)]
+    debug_luma: Option<String>,
+
+    /// Threads each AI session may use inside one operator
+    #[arg(long, default_value_t = 1)]
+    intra_threads: usize,
+
+    /// Graph optimizations applied when the model is loaded
+    #[arg(long, value_enum, default_value_t = GraphOpt::All)]
+    graph_opt: GraphOpt,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum UpscalerChoice {
+    Onnx,
+    Lanczos3,
+    Bicubic,
+    Nearest,
+    EdgeDirected,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum GraphOpt {
+    Disabled,
+    Basic,
+    Extended,
+    All,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum AudioHandling {
+    Auto,
+    Copy,
+    Transcode,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum AudioTarget {
+    Aac,
+    Opus,
+    Flac,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Depth {
+    Auto,
+    #[value(name = "8")]
+    Eight,
+    #[value(name = "16")]
+    Sixteen,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum PixFmt {
+    Yuv420p,
+    Yuv420p10,
+    Yuv422p10,
+    Yuv444p10,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Scale {
+    Stretch,
+    Fit,
+    Fill,
+    Factor,
+    Width,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Codec {
+    H264,
+    Hevc,
+    Av1,
+    SvtAv1,
+    Vp9,
+    Prores,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum RateMode {
+    Cbr,
+    Abr,
+    Crf,
+    Qp,
+}
+
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .ok_or_else(|| format!("expected key=value, got '{}'", s))
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Normalization {
+    Clamp01,
+    Clamp255,
+    Minmax,
+    MinmaxSmooth,
+    Meanstd,
+}
+
+impl Args {
+    fn upscaler_kind(&self) -> UpscalerKind {
+        match self.upscaler {
+            UpscalerChoice::Onnx => UpscalerKind::Onnx,
+            UpscalerChoice::Lanczos3 => UpscalerKind::Lanczos3,
+            UpscalerChoice::Bicubic => UpscalerKind::Bicubic,
+            UpscalerChoice::Nearest => UpscalerKind::Nearest,
+            UpscalerChoice::EdgeDirected => UpscalerKind::EdgeDirected,
+        }
+    }
+
+    fn scale_mode(&self) -> ScaleMode {
+        let (width, height) = (self.width, self.height);
+        match self.scale {
+            Scale::Stretch => ScaleMode::Stretch { width, height },
+            Scale::Fit => ScaleMode::Fit { width, height },
+            Scale::Fill => ScaleMode::Fill { width, height },
+            Scale::Factor => ScaleMode::Factor(self.factor),
+            Scale::Width => ScaleMode::Width(width),
+        }
+    }
+
+    fn normalization_mode(&self) -> NormalizationMode {
+        match self.normalization {
+            Normalization::Clamp01 => NormalizationMode::ClampUnit,
+            Normalization::Clamp255 => NormalizationMode::Clamp255,
+            Normalization::Minmax => NormalizationMode::MinMax,
+            Normalization::MinmaxSmooth => NormalizationMode::MinMaxSmoothed { alpha: self.norm_smoothing },
+            Normalization::Meanstd => NormalizationMode::MeanStd {
+                mean: [self.norm_mean; 3],
+                std: [self.norm_std; 3],
+            },
+        }
+    }
+
+    fn encoder_options(&self) -> Result<EncoderOptions> {
+        Ok(EncoderOptions {
+            codec: match self.codec {
+                Codec::H264 => VideoCodec::H264,
+                Codec::Hevc => VideoCodec::Hevc,
+                Codec::Av1 => VideoCodec::Av1Aom,
+                Codec::SvtAv1 => VideoCodec::Av1Svt,
+                Codec::Vp9 => VideoCodec::Vp9,
+                Codec::Prores => VideoCodec::ProRes,
+            },
+            container: self.container.clone(),
+            rate_control: match self.rate_control {
+                RateMode::Cbr => RateControl::Cbr(self.bitrate),
+                RateMode::Abr => RateControl::Abr(self.bitrate),
+                RateMode::Crf => RateControl::Crf(self.quality),
+                RateMode::Qp => RateControl::Qp(self.qp()?),
+            },
+            preset: self.preset.clone(),
+            tune: self.tune.clone(),
+            profile: self.profile.clone(),
+            level: self.level.clone(),
+            gop_size: self.gop,
+            max_b_frames: self.bframes,
+            pixel_format: self.pix_fmt.map(|f| match f {
+                PixFmt::Yuv420p => OutputPixelFormat::Yuv420p,
+                PixFmt::Yuv420p10 => OutputPixelFormat::Yuv420p10,
+                PixFmt::Yuv422p10 => OutputPixelFormat::Yuv422p10,
+                PixFmt::Yuv444p10 => OutputPixelFormat::Yuv444p10,
+            }),
+            private_options: self.encoder_opts.clone(),
+        })
+    }
+
+    // QP is an integer quantizer; a fractional or negative --quality is refused rather than truncated
+    fn qp(&self) -> Result<u32> {
+        if self.quality < 0.0 || self.quality.fract() != 0.0 || self.quality > u32::MAX as f32 {
+            return Err(anyhow!("--quality must be a whole number of at least 0 for qp rate control, got {}", self.quality));
+        }
+        Ok(self.quality as u32)
+    }
+
+    fn audio_options(&self) -> AudioOptions {
+        AudioOptions {
+            mode: match self.audio {
+                AudioHandling::Auto => AudioMode::Auto,
+                AudioHandling::Copy => AudioMode::Copy,
+                AudioHandling::Transcode => AudioMode::Transcode,
+            },
+            codec: match self.audio_codec {
+                AudioTarget::Aac => AudioCodec::Aac,
+                AudioTarget::Opus => AudioCodec::Opus,
+                AudioTarget::Flac => AudioCodec::Flac,
+            },
+            sample_rate: self.audio_rate,
+            channels: self.audio_channels,
+            bitrate: self.audio_bitrate,
+        }
+    }
+
+    fn stream_selection(&self) -> StreamSelection {
+        StreamSelection {
+            audio: !self.no_audio,
+            subtitles: !self.no_subtitles,
+            data: !self.no_data,
+            attachments: !self.no_attachments,
+            extra_video: !self.no_extra_video,
+            languages: self.languages.clone(),
+            include: self.include_streams.clone(),
+            exclude: self.exclude_streams.clone(),
+        }
+    }
+
+    fn scene_detection(&self) -> Option<SceneDetection> {
+        if self.scene_threshold.is_none() && self.scene_list.is_none() {
+            return None;
+        }
+        let defaults = SceneDetection::default();
+        Some(SceneDetection {
+            threshold: self.scene_threshold.unwrap_or(defaults.threshold),
+            sidecar: self.scene_list.clone(),
+        })
+    }
+
+    fn session_options(&self) -> SessionOptions {
+        SessionOptions {
+            intra_threads: self.intra_threads,
+            optimization: match self.graph_opt {
+                GraphOpt::Disabled => OptimizationLevel::Disabled,
+                GraphOpt::Basic => OptimizationLevel::Basic,
+                GraphOpt::Extended => OptimizationLevel::Extended,
+                GraphOpt::All => OptimizationLevel::All,
+            },
+        }
+    }
+
+    fn bit_depth(&self) -> BitDepth {
+        match self.bit_depth {
+            Depth::Auto => BitDepth::Auto,
+            Depth::Eight => BitDepth::Eight,
+            Depth::Sixteen => BitDepth::Sixteen,
+        }
+    }
+}
+
+const BAR_WIDTH: usize = 30;
+
+// One-line progress bar, redrawn in place
+fn render_progress(event: &ProgressEvent) {
+    let mut line = match (event.fraction(), event.total_frames) {
+        (Some(fraction), Some(total)) => {
+            let filled = (fraction * BAR_WIDTH as f64) as usize;
+            format!("🚀 [{}{}] {:5.1}% {}/{} frames",
+                "#".repeat(filled), "-".repeat(BAR_WIDTH - filled), fraction * 100.0, event.encoded, total)
+        }
+        _ => format!("🚀 {} frames", event.encoded),
+    };
+    line.push_str(&format!(" | {:.1} fps | {:.1} MB", event.fps, event.output_bytes as f64 / 1_000_000.0));
+    if let Some(eta) = event.eta.filter(|_| !event.finished) {
+        line.push_str(&format!(" | ETA {}", format_duration(eta)));
+    }
+    if event.finished {
+        line.push_str(&format!(" | took {}", format_duration(event.elapsed)));
+    }
+    print!("\r{:<100}", line);
+    std::io::stdout().flush().ok();
+}
+
+fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
 }
 
 #[tokio::main]
@@ -23,16 +485,56 @@ async fn main() -> Result<()> {
         eprintln!("❌ Error: Input file '{}' not found.", args.input);
         return Ok(());
     }
+    let upscaler = args.upscaler_kind();
+    if upscaler.needs_model() && !std::path::Path::new(&args.model).exists() {
+        eprintln!("❌ Error: Model file '{}' not found (use --upscaler lanczos3 or another model-free backend).", args.model);
+        return Ok(());
+    }
 
+    let scale = args.scale_mode();
+    let normalization = args.normalization_mode();
+    let encoder = args.encoder_options()?;
+    let audio = args.audio_options();
+    let streams = args.stream_selection();
+    let bit_depth = args.bit_depth();
+    let session = args.session_options();
+    let scene_detection = args.scene_detection();
     let config = Config {
         input_path: args.input,
         output_path: args.output,
-        model_path: "model.onnx".to_string(),
-        target_resolution: (1920, 1080),
+        model_path: args.model,
+        upscaler,
+        scale,
+        tiling: args.tiled.then_some(TileConfig { size: args.tile_size, overlap: args.tile_overlap }),
+        normalization,
+        encoder,
+        audio,
+        streams,
+        bit_depth,
+        batch_size: args.batch_size,
+        workers: args.workers,
+        stabilization: args.stabilize,
+        scene_detection,
+        debug_luma: args.debug_luma,
+        session,
     };
 
-    let engine = Engine::new(config)?;
-    engine.run().await?;
+    let engine = Engine::new(config)?.with_progress(render_progress);
+
+    // First Ctrl-C finishes the frames in flight and closes the output properly, a second one aborts
+    let cancel = engine.cancel_token();
+    ctrlc::set_handler(move || {
+        if cancel.is_cancelled() {
+            std::process::exit(130);
+        }
+        eprintln!("\n🛑 Stopping, finishing the output (Ctrl-C again to abort)...");
+        cancel.cancel();
+    })?;
+
+    match engine.run().await {
+        Err(XStreamError::Cancelled) => std::process::exit(130),
+        result => result?,
+    }
 
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,341 @@
+// src/processing.rs
+
+use std::collections::VecDeque;
+use anyhow::{Result, anyhow};
+use crate::ai::processor::{upscale_grayscale, upscale_to_original, save_ppm};
+use crate::ai::upscaler::{Upscaled, Upscaler};
+use crate::video::color::ColorInfo;
+use crate::video::convert::YuvConverter;
+use crate::video::scale::ScalePlan;
+use crate::video::types::{ChromaSubsampling, RawFrame, Sample, UpscaledFrame};
+
+/// What every processor of a worker learns before the first frame.
+#[derive(Debug, Clone, Copy)]
+pub struct ProcessorContext {
+    pub plan: ScalePlan,       // crop, content placement and output size
+    pub color: ColorInfo,      // source color description, also used for the output
+    pub subsampling: ChromaSubsampling, // of the `UpscaledFrame` planes
+    pub worker: usize,         // index of the worker this instance runs on
+    pub workers: usize,
+}
+
+/// One step of the frame stage between decoder and encoder. Each worker builds its
+/// own instances, so implementations need not be `Send`. Frames are spread over the
+/// workers, so a processor only sees consecutive frames with a single worker; state
+/// carried between frames should be dropped at frames marked `scene_cut`.
+///
+/// Every frame handed to `process` must come out exactly once and in the order it
+/// went in, either from `process` or, if the step holds frames back (temporal
+/// filters), from `flush` at the end of the stream.
+pub trait FrameProcessor<In, Out = In> {
+    /// One line for the log.
+    fn name(&self) -> String {
+        "custom processor".to_string()
+    }
+
+    /// Called once before the first frame.
+    fn init(&mut self, _ctx: &ProcessorContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Frames `process` prefers at once; the chain uses the upscaler's.
+    fn batch_size(&self) -> usize {
+        1
+    }
+
+    /// Processes a non-empty batch, in order.
+    fn process(&mut self, frames: Vec<In>) -> Result<Vec<Out>>;
+
+    /// Returns whatever is still held back once the input has ended.
+    fn flush(&mut self) -> Result<Vec<Out>> {
+        Ok(Vec::new())
+    }
+}
+
+type RawStep<'a, T> = Box<dyn FrameProcessor<RawFrame<T>> + 'a>;
+type UpscaleStep<'a, T> = Box<dyn FrameProcessor<RawFrame<T>, UpscaledFrame<T>> + 'a>;
+type OutputStep<'a, T> = Box<dyn FrameProcessor<UpscaledFrame<T>> + 'a>;
+
+/// Processors run in sequence: RGB filters on the decoded frames (denoise, deinterlace),
+/// the step that turns them into output-size YUV (the upscaler), then filters on
+/// the result (sharpen, grain).
+pub struct ProcessorChain<'a, T: Sample> {
+    before: Vec<RawStep<'a, T>>,
+    upscale: UpscaleStep<'a, T>,
+    after: Vec<OutputStep<'a, T>>,
+}
+
+impl<'a, T: Sample> ProcessorChain<'a, T> {
+    pub fn new(upscale: impl FrameProcessor<RawFrame<T>, UpscaledFrame<T>> + 'a) -> Self {
+        Self { before: Vec::new(), upscale: Box::new(upscale), after: Vec::new() }
+    }
+
+    /// Adds a filter on decoded RGB frames, after those added before it.
+    pub fn before(&mut self, step: impl FrameProcessor<RawFrame<T>> + 'a) -> &mut Self {
+        self.before.push(Box::new(step));
+        self
+    }
+
+    /// Adds a filter on upscaled YUV frames, after those added before it.
+    pub fn after(&mut self, step: impl FrameProcessor<UpscaledFrame<T>> + 'a) -> &mut Self {
+        self.after.push(Box::new(step));
+        self
+    }
+
+    /// Swaps out the built-in upscaling step.
+    pub fn replace_upscaler(&mut self, upscale: impl FrameProcessor<RawFrame<T>, UpscaledFrame<T>> + 'a) -> &mut Self {
+        self.upscale = Box::new(upscale);
+        self
+    }
+}
+
+// Runs `frames` (if any) through `step`, then flushes it
+fn drain_step<In, Out>(step: &mut (impl FrameProcessor<In, Out> + ?Sized), frames: Vec<In>) -> Result<Vec<Out>> {
+    let mut out = if frames.is_empty() { Vec::new() } else { step.process(frames)? };
+    out.extend(step.flush()?);
+    Ok(out)
+}
+
+impl<T: Sample> FrameProcessor<RawFrame<T>, UpscaledFrame<T>> for ProcessorChain<'_, T> {
+    fn name(&self) -> String {
+        let before = self.before.iter().map(|s| s.name());
+        let after = self.after.iter().map(|s| s.name());
+        before.chain(std::iter::once(self.upscale.name())).chain(after).collect::<Vec<_>>().join(" → ")
+    }
+
+    fn init(&mut self, ctx: &ProcessorContext) -> Result<()> {
+        for step in &mut self.before {
+            step.init(ctx)?;
+        }
+        self.upscale.init(ctx)?;
+        for step in &mut self.after {
+            step.init(ctx)?;
+        }
+        Ok(())
+    }
+
+    fn batch_size(&self) -> usize {
+        self.upscale.batch_size()
+    }
+
+    fn process(&mut self, frames: Vec<RawFrame<T>>) -> Result<Vec<UpscaledFrame<T>>> {
+        let mut raw = frames;
+        for step in &mut self.before {
+            if raw.is_empty() {
+                return Ok(Vec::new());
+            }
+            raw = step.process(raw)?;
+        }
+        if raw.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut up = self.upscale.process(raw)?;
+        for step in &mut self.after {
+            if up.is_empty() {
+                break;
+            }
+            up = step.process(up)?;
+        }
+        Ok(up)
+    }
+
+    // Each step is flushed after everything held back upstream went through it
+    fn flush(&mut self) -> Result<Vec<UpscaledFrame<T>>> {
+        let mut raw = Vec::new();
+        for step in &mut self.before {
+            raw = drain_step(step.as_mut(), raw)?;
+        }
+        let mut up = drain_step(self.upscale.as_mut(), raw)?;
+        for step in &mut self.after {
+            up = drain_step(step.as_mut(), up)?;
+        }
+        Ok(up)
+    }
+}
+
+/// Steps run one after another as a single processor.
+impl<In, P: FrameProcessor<In> + ?Sized> FrameProcessor<In> for Vec<Box<P>> {
+    fn name(&self) -> String {
+        self.iter().map(|s| s.name()).collect::<Vec<_>>().join(" → ")
+    }
+
+    fn init(&mut self, ctx: &ProcessorContext) -> Result<()> {
+        self.iter_mut().try_for_each(|step| step.init(ctx))
+    }
+
+    fn process(&mut self, frames: Vec<In>) -> Result<Vec<In>> {
+        let mut frames = frames;
+        for step in self.iter_mut() {
+            if frames.is_empty() {
+                break;
+            }
+            frames = step.process(frames)?;
+        }
+        Ok(frames)
+    }
+
+    fn flush(&mut self) -> Result<Vec<In>> {
+        let mut frames = Vec::new();
+        for step in self.iter_mut() {
+            frames = drain_step(step.as_mut(), frames)?;
+        }
+        Ok(frames)
+    }
+}
+
+/// Writes the luma plane of the first frame it sees to a grayscale PPM, to check
+/// the upscaled picture without decoding the output.
+pub struct LumaDump {
+    path: String,
+    written: bool,
+}
+
+impl LumaDump {
+    pub fn new(path: &str) -> Self {
+        Self { path: path.to_string(), written: false }
+    }
+}
+
+impl<T: Sample> FrameProcessor<UpscaledFrame<T>> for LumaDump {
+    fn name(&self) -> String {
+        format!("luma dump to {}", self.path)
+    }
+
+    fn process(&mut self, frames: Vec<UpscaledFrame<T>>) -> Result<Vec<UpscaledFrame<T>>> {
+        if let (false, Some(frame)) = (self.written, frames.first()) {
+            let y_size = (frame.width * frame.height) as usize;
+            let mut debug_rgb = Vec::with_capacity(y_size * 3);
+            for &y_sample in frame.data.iter().take(y_size) {
+                let y_pixel = (y_sample.to_unit() * 255.0) as u8;
+                debug_rgb.extend([y_pixel; 3]);
+            }
+            save_ppm(&self.path, &debug_rgb, frame.width, frame.height)?;
+            self.written = true;
+        }
+        Ok(frames)
+    }
+}
+
+/// Adds custom processors to the chain of every worker. Written once for both
+/// sample depths; register it with `Engine::with_processors`.
+pub trait BuildChain: Send + Sync {
+    fn build<T: Sample>(&self, ctx: &ProcessorContext, chain: &mut ProcessorChain<'_, T>) -> Result<()>;
+}
+
+// Object-safe face of `BuildChain`, so the engine can store one
+pub(crate) trait ChainBuilder: Send + Sync {
+    fn build_u8(&self, ctx: &ProcessorContext, chain: &mut ProcessorChain<'_, u8>) -> Result<()>;
+    fn build_u16(&self, ctx: &ProcessorContext, chain: &mut ProcessorChain<'_, u16>) -> Result<()>;
+}
+
+impl<B: BuildChain> ChainBuilder for B {
+    fn build_u8(&self, ctx: &ProcessorContext, chain: &mut ProcessorChain<'_, u8>) -> Result<()> {
+        self.build(ctx, chain)
+    }
+
+    fn build_u16(&self, ctx: &ProcessorContext, chain: &mut ProcessorChain<'_, u16>) -> Result<()> {
+        self.build(ctx, chain)
+    }
+}
+
+/// Sample types a `ChainBuilder` can build for.
+pub(crate) trait ChainSample: Sample {
+    fn build_chain(builder: &dyn ChainBuilder, ctx: &ProcessorContext, chain: &mut ProcessorChain<'_, Self>) -> Result<()>;
+}
+
+impl ChainSample for u8 {
+    fn build_chain(builder: &dyn ChainBuilder, ctx: &ProcessorContext, chain: &mut ProcessorChain<'_, u8>) -> Result<()> {
+        builder.build_u8(ctx, chain)
+    }
+}
+
+impl ChainSample for u16 {
+    fn build_chain(builder: &dyn ChainBuilder, ctx: &ProcessorContext, chain: &mut ProcessorChain<'_, u16>) -> Result<()> {
+        builder.build_u16(ctx, chain)
+    }
+}
+
+/// The built-in middle of the chain: runs an `Upscaler`, resizes its output to the
+/// content size, puts luma-only results together with chroma from the source and
+/// converts to padded planar YUV in the output's matrix and range.
+pub struct UpscaleStage<'a, T: Sample> {
+    upscaler: Box<dyn Upscaler<T> + 'a>,
+    converter: Option<(YuvConverter<T>, ScalePlan, ColorInfo)>, // set up by init
+    pending: VecDeque<RawFrame<T>>, // source frames whose results the upscaler still owes
+}
+
+impl<'a, T: Sample> UpscaleStage<'a, T> {
+    pub fn new(upscaler: Box<dyn Upscaler<T> + 'a>) -> Self {
+        Self { upscaler, converter: None, pending: VecDeque::new() }
+    }
+
+    // Pairs upscaler results with their source frames, oldest first
+    fn finish(&mut self, outputs: Vec<Upscaled<T>>) -> Result<Vec<UpscaledFrame<T>>> {
+        let (yuv_converter, plan, color) = self.converter.as_mut().ok_or_else(|| anyhow!("Upscale stage used before init"))?;
+        let (tw, th) = plan.output;
+        let (cw, ch) = (plan.content.width as i32, plan.content.height as i32);
+        let y_size = (tw * th) as usize;
+
+        let mut result = Vec::with_capacity(outputs.len());
+        for output in outputs {
+            let raw = self.pending.pop_front().ok_or_else(|| anyhow!("{} returned more frames than it was given", self.upscaler.describe()))?;
+            let yuv_data = match output {
+                // Full-color output already carries chroma
+                Upscaled::Rgb { data, width, height } => {
+                    let rgb = if (width, height) == (cw, ch) { data } else { upscale_to_original(&data, width, height, cw, ch) };
+                    yuv_converter.convert(&plan.pad(rgb, 3))?
+                }
+                Upscaled::Luma { data, width, height } => {
+                    let ai_y_upscaled = plan.pad(upscale_grayscale(&data, width, height, cw, ch), 1);
+                    let raw_upscaled_rgb = upscale_to_original(&raw.data, raw.width, raw.height, cw, ch);
+                    let mut yuv_data = yuv_converter.convert(&plan.pad(raw_upscaled_rgb, 3))?;
+                    if ai_y_upscaled.len() == y_size && yuv_data.len() >= y_size {
+                        for (dst, y) in yuv_data[0..y_size].iter_mut().zip(&ai_y_upscaled) {
+                            *dst = color.encode_luma(y.to_unit());
+                        }
+                    }
+                    yuv_data
+                }
+            };
+            result.push(UpscaledFrame {
+                data: yuv_data,
+                width: tw as i32,
+                height: th as i32,
+                pts: raw.pts,
+                duration: raw.duration,
+                time_base: raw.time_base,
+                scene_cut: raw.scene_cut,
+            });
+        }
+        Ok(result)
+    }
+}
+
+impl<T: Sample> FrameProcessor<RawFrame<T>, UpscaledFrame<T>> for UpscaleStage<'_, T> {
+    fn name(&self) -> String {
+        self.upscaler.describe()
+    }
+
+    fn init(&mut self, ctx: &ProcessorContext) -> Result<()> {
+        let (tw, th) = ctx.plan.output;
+        let converter = YuvConverter::<T>::new(tw as i32, th as i32, ctx.subsampling, &ctx.color)?;
+        self.converter = Some((converter, ctx.plan, ctx.color));
+        Ok(())
+    }
+
+    fn batch_size(&self) -> usize {
+        self.upscaler.batch_size()
+    }
+
+    fn process(&mut self, frames: Vec<RawFrame<T>>) -> Result<Vec<UpscaledFrame<T>>> {
+        let content = self.converter.as_ref().map(|(_, plan, _)| plan.content).ok_or_else(|| anyhow!("Upscale stage used before init"))?;
+        let outputs = self.upscaler.upscale(&frames, content.width as i32, content.height as i32)?;
+        self.pending.extend(frames);
+        self.finish(outputs)
+    }
+
+    fn flush(&mut self) -> Result<Vec<UpscaledFrame<T>>> {
+        let outputs = self.upscaler.flush()?;
+        self.finish(outputs)
+    }
+}
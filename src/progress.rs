@@ -0,0 +1,105 @@
+// src/progress.rs
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use crossbeam_channel::{Receiver, RecvTimeoutError};
+
+/// How often a running pipeline reports progress.
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Snapshot of a running pipeline, sent every `PROGRESS_INTERVAL` and once more at the end.
+#[derive(Debug, Clone)]
+pub struct ProgressEvent {
+    pub decoded: u64,              // video frames out of the decoder
+    pub inferred: u64,             // frames through the model
+    pub encoded: u64,              // frames handed to the encoder
+    pub total_frames: Option<u64>, // estimate from the stream's frame count or duration
+    pub fps: f64,                  // encoded frames per second since the start
+    pub eta: Option<Duration>,     // None until there is a total and a rate
+    pub output_bytes: u64,         // written to the output file so far
+    pub elapsed: Duration,
+    pub finished: bool,            // last event of the run
+}
+
+impl ProgressEvent {
+    /// Encoded share of the estimated total (0-1), if there is one.
+    pub fn fraction(&self) -> Option<f64> {
+        self.total_frames
+            .filter(|&total| total > 0)
+            .map(|total| (self.encoded as f64 / total as f64).min(1.0))
+    }
+}
+
+/// Called with each progress event, on a thread of its own.
+pub type ProgressCallback = Arc<dyn Fn(&ProgressEvent) + Send + Sync>;
+
+/// Counters the pipeline stages bump as frames pass through; cheap to clone.
+#[derive(Clone, Default)]
+pub struct ProgressCounters {
+    decoded: Arc<AtomicU64>,
+    inferred: Arc<AtomicU64>,
+    encoded: Arc<AtomicU64>,
+    output_bytes: Arc<AtomicU64>,
+}
+
+impl ProgressCounters {
+    pub fn add_decoded(&self) {
+        self.decoded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_inferred(&self) {
+        self.inferred.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_encoded(&self) {
+        self.encoded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_output_bytes(&self, bytes: u64) {
+        self.output_bytes.store(bytes, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self, total_frames: Option<u64>, elapsed: Duration, finished: bool) -> ProgressEvent {
+        let encoded = self.encoded.load(Ordering::Relaxed);
+        let secs = elapsed.as_secs_f64();
+        let fps = if secs > 0.0 { encoded as f64 / secs } else { 0.0 };
+        let eta = match total_frames {
+            Some(total) if fps > 0.0 => Some(Duration::from_secs_f64(total.saturating_sub(encoded) as f64 / fps)),
+            _ => None,
+        };
+        ProgressEvent {
+            decoded: self.decoded.load(Ordering::Relaxed),
+            inferred: self.inferred.load(Ordering::Relaxed),
+            encoded,
+            total_frames,
+            fps,
+            eta,
+            output_bytes: self.output_bytes.load(Ordering::Relaxed),
+            elapsed,
+            finished,
+        }
+    }
+}
+
+/// Frame count of a stream: its own `nb_frames` if the container stores one,
+/// otherwise duration times frame rate. None if neither is known.
+pub fn estimate_total_frames(nb_frames: i64, duration_secs: Option<f64>, fps: f64) -> Option<u64> {
+    if nb_frames > 0 {
+        return Some(nb_frames as u64);
+    }
+    match duration_secs {
+        Some(secs) if secs > 0.0 && fps > 0.0 => Some((secs * fps).round() as u64),
+        _ => None,
+    }
+}
+
+/// Sends `callback` a snapshot of `counters` every `PROGRESS_INTERVAL` until `stop`
+/// is dropped, then a final one marked `finished`.
+pub fn run_reporter(counters: ProgressCounters, total_frames: Option<u64>, callback: ProgressCallback, stop: Receiver<()>) {
+    let start = Instant::now();
+    while let Err(RecvTimeoutError::Timeout) = stop.recv_timeout(PROGRESS_INTERVAL) {
+        callback(&counters.snapshot(total_frames, start.elapsed(), false));
+    }
+    callback(&counters.snapshot(total_frames, start.elapsed(), true));
+}
@@ -0,0 +1,282 @@
+// src/video/audio.rs
+
+use rsmpeg::ffi;
+use anyhow::{Context, Result, anyhow};
+use crate::video::types::PacketData;
+use crate::video::averror::check;
+use crate::video::wrappers::{
+    find_encoder, rescale_q, SafeAudioFifo, SafeCodecContext, SafeDictionary, SafeFormatContextOutput,
+    SafeFrame, SafePacket, SafeSwrContext,
+};
+
+/// Codec audio is re-encoded to when it is not stream-copied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AudioCodec {
+    #[default]
+    Aac,
+    Opus,
+    Flac,
+}
+
+impl AudioCodec {
+    /// FFmpeg encoder name, preferred over the generic codec id lookup.
+    pub fn encoder_name(&self) -> &'static str {
+        match self {
+            AudioCodec::Aac => "aac",
+            AudioCodec::Opus => "libopus",
+            AudioCodec::Flac => "flac",
+        }
+    }
+
+    pub fn codec_id(&self) -> ffi::AVCodecID {
+        match self {
+            AudioCodec::Aac => ffi::AV_CODEC_ID_AAC,
+            AudioCodec::Opus => ffi::AV_CODEC_ID_OPUS,
+            AudioCodec::Flac => ffi::AV_CODEC_ID_FLAC,
+        }
+    }
+}
+
+/// Whether source audio is stream-copied or re-encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AudioMode {
+    /// Copy when the output container accepts the source codec, re-encode otherwise.
+    #[default]
+    Auto,
+    Copy,
+    Transcode,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AudioOptions {
+    pub mode: AudioMode,
+    pub codec: AudioCodec,
+    pub sample_rate: Option<i32>, // None = source rate, or the closest one the encoder supports
+    pub channels: Option<i32>,    // None = source channel layout
+    pub bitrate: Option<i64>,     // None = encoder default
+}
+
+impl AudioOptions {
+    /// Codec to re-encode a `source` stream to when muxing into `out_ctx`, None to copy it.
+    pub fn transcode_target(&self, out_ctx: &SafeFormatContextOutput, source: ffi::AVCodecID) -> Option<AudioCodec> {
+        match self.mode {
+            AudioMode::Copy => None,
+            AudioMode::Transcode => Some(self.codec),
+            AudioMode::Auto => {
+                // A muxer that keeps no codec list gets the copy attempted
+                if out_ctx.accepts_codec(source) != Some(false) {
+                    return None;
+                }
+                let fallback = [self.codec, AudioCodec::Opus, AudioCodec::Aac, AudioCodec::Flac]
+                    .into_iter()
+                    .find(|c| out_ctx.accepts_codec(c.codec_id()) == Some(true));
+                Some(fallback.unwrap_or(self.codec))
+            }
+        }
+    }
+}
+
+/// Decodes source audio packets, resamples them to what the target encoder takes
+/// and writes the re-encoded packets to the output stream.
+pub struct AudioTranscoder {
+    decoder: SafeCodecContext,
+    encoder: SafeCodecContext,
+    resampler: SafeSwrContext,
+    fifo: SafeAudioFifo, // resampled samples waiting for a full encoder frame
+    in_time_base: ffi::AVRational,
+    next_pts: i64,       // in encoder time base (1/sample_rate)
+    pkt: SafePacket,
+}
+
+impl AudioTranscoder {
+    /// Opens a decoder for the source stream's `in_par` and an encoder for `codec`.
+    pub fn new(
+        in_par: &ffi::AVCodecParameters,
+        in_time_base: ffi::AVRational,
+        codec: AudioCodec,
+        opts: &AudioOptions,
+        global_header: bool,
+    ) -> Result<Self> {
+        let decoder = SafeCodecContext::decoder(in_par, in_time_base)
+            .context("Failed to open the source audio decoder")?;
+
+        let encoder_codec = find_encoder(codec.encoder_name(), codec.codec_id())
+            .ok_or_else(|| anyhow!("No audio encoder available for {:?}", codec))?;
+        let mut encoder = SafeCodecContext::new(encoder_codec)?;
+        let rate = pick_sample_rate(encoder_codec, opts.sample_rate.unwrap_or(decoder.sample_rate));
+        encoder.sample_rate = rate;
+        encoder.sample_fmt = pick_sample_fmt(encoder_codec);
+        encoder.time_base = ffi::AVRational { num: 1, den: rate };
+        unsafe {
+            match opts.channels {
+                Some(channels) => ffi::av_channel_layout_default(&mut encoder.ch_layout, channels),
+                None => {
+                    check(ffi::av_channel_layout_copy(&mut encoder.ch_layout, &decoder.ch_layout), "av_channel_layout_copy")?;
+                }
+            }
+        }
+        if let Some(bitrate) = opts.bitrate {
+            encoder.bit_rate = bitrate;
+        }
+        if global_header {
+            encoder.flags |= ffi::AV_CODEC_FLAG_GLOBAL_HEADER as i32;
+        }
+        encoder.open(&mut SafeDictionary::new())
+            .with_context(|| format!("Failed to open audio encoder {}", codec.encoder_name()))?;
+
+        let resampler = SafeSwrContext::new(
+            &encoder.ch_layout, encoder.sample_fmt, rate,
+            &decoder.ch_layout, decoder.sample_fmt, decoder.sample_rate,
+        ).with_context(|| format!("Could not create audio resampler for {:?}", codec))?;
+        let fifo = SafeAudioFifo::new(encoder.sample_fmt, encoder.ch_layout.nb_channels)?;
+
+        Ok(Self {
+            decoder,
+            encoder,
+            resampler,
+            fifo,
+            in_time_base,
+            next_pts: ffi::AV_NOPTS_VALUE,
+            pkt: SafePacket::new()?,
+        })
+    }
+
+    /// Describes the encoded audio on the output stream; call before the header is written.
+    pub fn setup_stream(&self, stream: &mut ffi::AVStream) -> Result<()> {
+        self.encoder.copy_params_to(stream)?;
+        stream.time_base = self.encoder.time_base;
+        Ok(())
+    }
+
+    /// Decodes one source packet and writes whatever full encoder frames it completes
+    /// to output stream `stream`. The header must be written.
+    pub fn transcode(&mut self, packet: &PacketData, out_ctx: &mut SafeFormatContextOutput, stream: usize) -> Result<()> {
+        let pkt = SafePacket::from_data(packet)?;
+        // A corrupt packet is skipped, the decoder resyncs on the next one
+        if self.decoder.send_packet(Some(&pkt)).context("Audio decoding failed")? {
+            self.receive_decoded()?;
+        }
+        self.drain(false, out_ctx, stream)
+    }
+
+    /// Drains decoder, resampler and encoder at end of stream, before the trailer is written.
+    pub fn flush(&mut self, out_ctx: &mut SafeFormatContextOutput, stream: usize) -> Result<()> {
+        self.decoder.send_packet(None).context("Audio decoder flush failed")?;
+        self.receive_decoded()?;
+        self.resample(None)?;
+        self.drain(true, out_ctx, stream)
+    }
+
+    fn receive_decoded(&mut self) -> Result<()> {
+        let mut frame = SafeFrame::new()?;
+        while self.decoder.receive_frame(&mut frame)? {
+            if self.next_pts == ffi::AV_NOPTS_VALUE {
+                // Keep the source start offset so audio stays in sync with video
+                let pts = frame.best_effort_timestamp;
+                self.next_pts = if pts == ffi::AV_NOPTS_VALUE {
+                    0
+                } else {
+                    rescale_q(pts, self.in_time_base, self.encoder.time_base)
+                };
+            }
+            let res = self.resample(Some(&frame));
+            frame.unref();
+            res?;
+        }
+        Ok(())
+    }
+
+    // Converts a decoded frame (None = flush the resampler delay) into the FIFO
+    fn resample(&mut self, input: Option<&SafeFrame>) -> Result<()> {
+        let mut out = self.encoder_frame(0)?;
+        self.resampler.convert_frame(&mut out, input).context("Audio resampling failed")?;
+        if out.nb_samples > 0 {
+            self.fifo.write(&out)?;
+        }
+        Ok(())
+    }
+
+    // Encodes full frames from the FIFO; with `flush` also the short tail and the encoder delay
+    fn drain(&mut self, flush: bool, out_ctx: &mut SafeFormatContextOutput, stream: usize) -> Result<()> {
+        let variable = (self.encoder.codec().capabilities & ffi::AV_CODEC_CAP_VARIABLE_FRAME_SIZE as i32) != 0;
+        let frame_size = if variable { 0 } else { self.encoder.frame_size };
+        loop {
+            let available = self.fifo.size();
+            let n = if frame_size <= 0 || (flush && available < frame_size) {
+                available
+            } else if available >= frame_size {
+                frame_size
+            } else {
+                0
+            };
+            if n <= 0 {
+                break;
+            }
+            let mut chunk = self.encoder_frame(n)?;
+            chunk.alloc_buffers(0).context("Could not allocate an audio frame")?;
+            self.fifo.read(&mut chunk)?;
+            chunk.pts = self.next_pts;
+            self.next_pts += n as i64;
+            self.encode(Some(&chunk), out_ctx, stream)?;
+        }
+        if flush {
+            self.encode(None, out_ctx, stream)?;
+        }
+        Ok(())
+    }
+
+    fn encode(&mut self, frame: Option<&SafeFrame>, out_ctx: &mut SafeFormatContextOutput, stream: usize) -> Result<()> {
+        self.encoder.send_frame(frame).context("Audio encoding failed")?;
+        let out_time_base = out_ctx.stream(stream).map(|s| s.time_base)
+            .ok_or_else(|| anyhow!("Output has no stream #{}", stream))?;
+        while self.encoder.receive_packet(&mut self.pkt)? {
+            self.pkt.rescale_ts(self.encoder.time_base, out_time_base);
+            self.pkt.stream_index = stream as i32;
+            out_ctx.write_packet(&mut self.pkt)?;
+        }
+        Ok(())
+    }
+
+    // Frame in the encoder's sample format/layout/rate with `nb_samples` (0 = let swresample size it)
+    fn encoder_frame(&self, nb_samples: i32) -> Result<SafeFrame> {
+        let mut frame = SafeFrame::new()?;
+        unsafe {
+            check(ffi::av_channel_layout_copy(&mut frame.ch_layout, &self.encoder.ch_layout), "av_channel_layout_copy")?;
+        }
+        frame.format = self.encoder.sample_fmt;
+        frame.sample_rate = self.encoder.sample_rate;
+        frame.nb_samples = nb_samples;
+        Ok(frame)
+    }
+}
+
+// Returns `preferred` if the encoder supports it, otherwise its highest supported rate
+fn pick_sample_rate(codec: &ffi::AVCodec, preferred: i32) -> i32 {
+    let mut p = codec.supported_samplerates;
+    if p.is_null() {
+        return preferred;
+    }
+    let mut best = 0;
+    unsafe {
+        while *p != 0 {
+            if *p == preferred {
+                return preferred;
+            }
+            best = best.max(*p);
+            p = p.add(1);
+        }
+    }
+    if best > 0 { best } else { preferred }
+}
+
+// The encoder's first supported sample format (its native one)
+fn pick_sample_fmt(codec: &ffi::AVCodec) -> ffi::AVSampleFormat {
+    let list = codec.sample_fmts;
+    unsafe {
+        if list.is_null() || *list == ffi::AV_SAMPLE_FMT_NONE {
+            ffi::AV_SAMPLE_FMT_FLTP
+        } else {
+            *list
+        }
+    }
+}
@@ -0,0 +1,66 @@
+// src/video/averror.rs
+
+use rsmpeg::ffi;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use anyhow::{Result, anyhow};
+
+/// `AVERROR(EAGAIN)`: the codec needs more input, or its output drained first.
+pub const AVERROR_EAGAIN: i32 = -libc::EAGAIN;
+
+/// Readable text for an FFmpeg error code, as `av_strerror` gives it.
+pub fn av_error_string(code: i32) -> String {
+    let mut buf = [0 as c_char; ffi::AV_ERROR_MAX_STRING_SIZE as usize];
+    unsafe {
+        if ffi::av_strerror(code, buf.as_mut_ptr(), buf.len()) < 0 {
+            return format!("unknown error {}", code);
+        }
+        CStr::from_ptr(buf.as_ptr()).to_string_lossy().into_owned()
+    }
+}
+
+/// Passes a non-negative return code through; a negative one becomes an error
+/// naming the failed `call`.
+pub fn check(ret: i32, call: &str) -> Result<i32> {
+    if ret < 0 {
+        Err(anyhow!("{} failed: {} ({})", call, av_error_string(ret), ret))
+    } else {
+        Ok(ret)
+    }
+}
+
+/// For `avcodec_receive_*` loops: true if a frame or packet came out, false once
+/// the codec wants more input or is fully drained, an error for anything else.
+pub fn received(ret: i32, call: &str) -> Result<bool> {
+    match ret {
+        0 => Ok(true),
+        AVERROR_EAGAIN | ffi::AVERROR_EOF => Ok(false),
+        _ => check(ret, call).map(|_| false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_passes_non_negative_codes_through() {
+        assert_eq!(check(0, "avcodec_open2").unwrap(), 0);
+        assert_eq!(check(42, "av_write_frame").unwrap(), 42);
+    }
+
+    #[test]
+    fn check_names_the_call_and_code() {
+        let err = check(ffi::AVERROR_INVALIDDATA, "avcodec_send_packet").unwrap_err().to_string();
+        assert!(err.starts_with("avcodec_send_packet failed: "), "{}", err);
+        assert!(err.ends_with(&format!("({})", ffi::AVERROR_INVALIDDATA)), "{}", err);
+    }
+
+    #[test]
+    fn received_stops_on_eagain_and_eof_only() {
+        assert!(received(0, "avcodec_receive_frame").unwrap());
+        assert!(!received(AVERROR_EAGAIN, "avcodec_receive_frame").unwrap());
+        assert!(!received(ffi::AVERROR_EOF, "avcodec_receive_frame").unwrap());
+        assert!(received(ffi::AVERROR_INVALIDDATA, "avcodec_receive_frame").is_err());
+    }
+}
@@ -0,0 +1,181 @@
+// src/video/color.rs
+
+use rsmpeg::ffi;
+use std::ptr;
+use crate::video::types::Sample;
+
+/// Color description of the video stream: the tags FFmpeg keeps on the codec
+/// parameters plus HDR static metadata. Read from the input and written unchanged
+/// to the output, so BT.709/BT.2020 and PQ/HLG content survive the round trip.
+#[derive(Debug, Clone, Copy)]
+pub struct ColorInfo {
+    pub primaries: ffi::AVColorPrimaries,
+    pub transfer: ffi::AVColorTransferCharacteristic,
+    pub space: ffi::AVColorSpace,
+    pub range: ffi::AVColorRange,
+    pub mastering_display: Option<ffi::AVMasteringDisplayMetadata>,
+    pub content_light: Option<ffi::AVContentLightMetadata>,
+}
+
+impl Default for ColorInfo {
+    fn default() -> Self {
+        Self {
+            primaries: ffi::AVCOL_PRI_UNSPECIFIED,
+            transfer: ffi::AVCOL_TRC_UNSPECIFIED,
+            space: ffi::AVCOL_SPC_UNSPECIFIED,
+            range: ffi::AVCOL_RANGE_UNSPECIFIED,
+            mastering_display: None,
+            content_light: None,
+        }
+    }
+}
+
+impl ColorInfo {
+    /// Reads the color tags and HDR side data of a stream's codec parameters.
+    pub fn from_codecpar(par: &ffi::AVCodecParameters) -> Self {
+        let sd = par.coded_side_data;
+        let nb_sd = par.nb_coded_side_data;
+        unsafe {
+            let mastering = ffi::av_packet_side_data_get(sd, nb_sd, ffi::AV_PKT_DATA_MASTERING_DISPLAY_METADATA);
+            let light = ffi::av_packet_side_data_get(sd, nb_sd, ffi::AV_PKT_DATA_CONTENT_LIGHT_LEVEL);
+            Self {
+                primaries: par.color_primaries,
+                transfer: par.color_trc,
+                space: par.color_space,
+                range: par.color_range,
+                mastering_display: read_side_data::<ffi::AVMasteringDisplayMetadata>(mastering),
+                content_light: read_side_data::<ffi::AVContentLightMetadata>(light),
+            }
+        }
+    }
+
+    /// Fills in what the input leaves unspecified with FFmpeg's usual assumptions for
+    /// a frame of `height` lines: BT.601 below 720p, BT.709 above, limited range.
+    /// The output is tagged explicitly, since after upscaling a player would guess differently.
+    pub fn resolve(mut self, height: i32) -> Self {
+        if self.space == ffi::AVCOL_SPC_UNSPECIFIED {
+            self.space = if height >= 720 { ffi::AVCOL_SPC_BT709 } else { ffi::AVCOL_SPC_SMPTE170M };
+        }
+        if self.range == ffi::AVCOL_RANGE_UNSPECIFIED {
+            self.range = ffi::AVCOL_RANGE_MPEG;
+        }
+        self
+    }
+
+    /// True for PQ (SMPTE ST 2084) and HLG (ARIB STD-B67) transfer functions.
+    pub fn is_hdr(&self) -> bool {
+        self.transfer == ffi::AVCOL_TRC_SMPTE2084 || self.transfer == ffi::AVCOL_TRC_ARIB_STD_B67
+    }
+
+    pub fn is_full_range(&self) -> bool {
+        self.range == ffi::AVCOL_RANGE_JPEG
+    }
+
+    /// Luma weights (Kr, Kg, Kb) of the YUV matrix.
+    pub fn luma_weights(&self) -> [f32; 3] {
+        let (kr, kb) = match self.space {
+            ffi::AVCOL_SPC_BT709 => (0.2126, 0.0722),
+            ffi::AVCOL_SPC_BT2020_NCL | ffi::AVCOL_SPC_BT2020_CL => (0.2627, 0.0593),
+            ffi::AVCOL_SPC_SMPTE240M => (0.212, 0.087),
+            ffi::AVCOL_SPC_FCC => (0.30, 0.11),
+            _ => (0.299, 0.114), // BT.601 (BT470BG / SMPTE170M)
+        };
+        [kr, 1.0 - kr - kb, kb]
+    }
+
+    /// swscale colorspace id (`SWS_CS_*`) matching the YUV matrix.
+    pub fn sws_colorspace(&self) -> i32 {
+        (match self.space {
+            ffi::AVCOL_SPC_BT709 => ffi::SWS_CS_ITU709,
+            ffi::AVCOL_SPC_BT2020_NCL | ffi::AVCOL_SPC_BT2020_CL => ffi::SWS_CS_BT2020,
+            ffi::AVCOL_SPC_SMPTE240M => ffi::SWS_CS_SMPTE240M,
+            ffi::AVCOL_SPC_FCC => ffi::SWS_CS_FCC,
+            _ => ffi::SWS_CS_DEFAULT,
+        }) as i32
+    }
+
+    /// Quantizes luma (0-1) into a sample of this stream's range.
+    pub fn encode_luma<T: Sample>(&self, y: f32) -> T {
+        if self.is_full_range() {
+            T::from_unit(y)
+        } else {
+            T::from_code8(16.0 + 219.0 * y.clamp(0.0, 1.0))
+        }
+    }
+
+    /// Sets the color tags and HDR side data on an encoder before it is opened.
+    pub fn apply_to_encoder(&self, ctx: &mut ffi::AVCodecContext) {
+        ctx.color_primaries = self.primaries;
+        ctx.color_trc = self.transfer;
+        ctx.colorspace = self.space;
+        ctx.color_range = self.range;
+        unsafe {
+            if let Some(md) = &self.mastering_display {
+                let sd = ffi::av_frame_side_data_new(
+                    &mut ctx.decoded_side_data, &mut ctx.nb_decoded_side_data,
+                    ffi::AV_FRAME_DATA_MASTERING_DISPLAY_METADATA, std::mem::size_of_val(md), 0
+                );
+                if !sd.is_null() {
+                    write_side_data((*sd).data, md);
+                }
+            }
+            if let Some(cll) = &self.content_light {
+                let sd = ffi::av_frame_side_data_new(
+                    &mut ctx.decoded_side_data, &mut ctx.nb_decoded_side_data,
+                    ffi::AV_FRAME_DATA_CONTENT_LIGHT_LEVEL, std::mem::size_of_val(cll), 0
+                );
+                if !sd.is_null() {
+                    write_side_data((*sd).data, cll);
+                }
+            }
+        }
+    }
+
+    /// Writes the color tags and HDR side data on an output stream before the header
+    /// is written, for the muxer (mdcv/clli boxes in MP4, MasteringMetadata in Matroska).
+    pub fn apply_to_stream(&self, par: &mut ffi::AVCodecParameters) {
+        par.color_primaries = self.primaries;
+        par.color_trc = self.transfer;
+        par.color_space = self.space;
+        par.color_range = self.range;
+        unsafe {
+            if let Some(md) = &self.mastering_display {
+                let sd = ffi::av_packet_side_data_new(
+                    &mut par.coded_side_data, &mut par.nb_coded_side_data,
+                    ffi::AV_PKT_DATA_MASTERING_DISPLAY_METADATA, std::mem::size_of_val(md), 0
+                );
+                if !sd.is_null() {
+                    write_side_data((*sd).data, md);
+                }
+            }
+            if let Some(cll) = &self.content_light {
+                let sd = ffi::av_packet_side_data_new(
+                    &mut par.coded_side_data, &mut par.nb_coded_side_data,
+                    ffi::AV_PKT_DATA_CONTENT_LIGHT_LEVEL, std::mem::size_of_val(cll), 0
+                );
+                if !sd.is_null() {
+                    write_side_data((*sd).data, cll);
+                }
+            }
+        }
+    }
+
+    /// Tags a frame about to be encoded.
+    pub fn apply_to_frame(&self, frame: &mut ffi::AVFrame) {
+        frame.color_primaries = self.primaries;
+        frame.color_trc = self.transfer;
+        frame.colorspace = self.space;
+        frame.color_range = self.range;
+    }
+}
+
+unsafe fn read_side_data<S: Copy>(sd: *const ffi::AVPacketSideData) -> Option<S> {
+    if sd.is_null() || (*sd).size < std::mem::size_of::<S>() {
+        return None;
+    }
+    Some(ptr::read_unaligned((*sd).data as *const S))
+}
+
+unsafe fn write_side_data<S>(dst: *mut u8, value: &S) {
+    ptr::copy_nonoverlapping(value as *const S as *const u8, dst, std::mem::size_of::<S>());
+}
@@ -0,0 +1,52 @@
+// src/video/convert.rs
+
+use rsmpeg::ffi;
+use std::marker::PhantomData;
+use anyhow::{Context, Result};
+use crate::video::color::ColorInfo;
+use crate::video::types::{ChromaSubsampling, Sample};
+use crate::video::wrappers::SafeSwsContext;
+
+/// Interleaved RGB to planar YUV through swscale: chroma is filtered rather than
+/// point-sampled, odd sizes round the chroma planes up, and the output uses the
+/// matrix and range of `color`. Build one per frame size and reuse it.
+pub struct YuvConverter<T: Sample> {
+    sws: SafeSwsContext,
+    width: i32,
+    height: i32,
+    subsampling: ChromaSubsampling,
+    _sample: PhantomData<T>,
+}
+
+impl<T: Sample> YuvConverter<T> {
+    pub fn new(width: i32, height: i32, subsampling: ChromaSubsampling, color: &ColorInfo) -> Result<Self> {
+        let mut sws = SafeSwsContext::new(
+            width, height, T::RGB_FORMAT,
+            width, height, T::yuv_format(subsampling),
+            (ffi::SWS_BICUBIC | ffi::SWS_FULL_CHR_H_INP | ffi::SWS_ACCURATE_RND) as i32
+        ).with_context(|| format!("Could not create RGB -> YUV converter for {}x{} ({:?})", width, height, subsampling))?;
+        sws.set_colorspace(color.sws_colorspace(), true, color.sws_colorspace(), color.is_full_range())?;
+        Ok(Self { sws, width, height, subsampling, _sample: PhantomData })
+    }
+
+    pub fn subsampling(&self) -> ChromaSubsampling {
+        self.subsampling
+    }
+
+    /// Total samples in one converted frame (Y, then U, then V, rows packed).
+    pub fn frame_len(&self) -> usize {
+        self.subsampling.plane_sizes(self.width, self.height)
+            .iter()
+            .map(|&(w, h)| (w * h) as usize)
+            .sum()
+    }
+
+    /// Converts a `width`x`height` interleaved RGB frame to packed planar YUV.
+    pub fn convert(&mut self, rgb: &[T]) -> Result<Vec<T>> {
+        let mut yuv = vec![T::default(); self.frame_len()];
+        let planes = self.subsampling.plane_sizes(self.width, self.height);
+        self.sws.packed_to_planes(rgb, self.width, self.height, &mut yuv, &planes)
+            .context("RGB to YUV conversion failed")?;
+        Ok(yuv)
+    }
+}
@@ -1,196 +1,441 @@
 // src/video/encoder.rs
 
 use rsmpeg::ffi;
-use std::ffi::CString;
-use std::ptr;
-use anyhow::{Result, anyhow};
+use anyhow::{Context, Result, anyhow};
 use crossbeam_channel::Receiver;
-use std::io::Write;
-use crate::video::types::{EncoderMsg};
+use crate::video::types::{ChromaSubsampling, EncoderMsg, Sample};
+use crate::video::color::ColorInfo;
+use crate::video::audio::{AudioOptions, AudioTranscoder};
+use crate::video::streams::{self, StreamSelection};
+use crate::video::wrappers::{
+    codec_name, codecpar, codecpar_mut, copy_codecpar, find_encoder, rescale_q, SafeCodecContext, SafeDictionary,
+    SafeFormatContextInput, SafeFormatContextOutput, SafeFrame, SafePacket, SafeSwsContext,
+};
+use crate::error::XStreamError;
+use crate::progress::ProgressCounters;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VideoCodec {
+    #[default]
+    H264,
+    Hevc,
+    Av1Aom,
+    Av1Svt,
+    Vp9,
+    ProRes,
+}
+
+impl VideoCodec {
+    /// Preferred FFmpeg encoder; if it is not compiled in, the default encoder for `codec_id` is used.
+    pub fn encoder_name(&self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "libx264",
+            VideoCodec::Hevc => "libx265",
+            VideoCodec::Av1Aom => "libaom-av1",
+            VideoCodec::Av1Svt => "libsvtav1",
+            VideoCodec::Vp9 => "libvpx-vp9",
+            VideoCodec::ProRes => "prores_ks",
+        }
+    }
+
+    pub fn codec_id(&self) -> ffi::AVCodecID {
+        match self {
+            VideoCodec::H264 => ffi::AV_CODEC_ID_H264,
+            VideoCodec::Hevc => ffi::AV_CODEC_ID_HEVC,
+            VideoCodec::Av1Aom | VideoCodec::Av1Svt => ffi::AV_CODEC_ID_AV1,
+            VideoCodec::Vp9 => ffi::AV_CODEC_ID_VP9,
+            VideoCodec::ProRes => ffi::AV_CODEC_ID_PRORES,
+        }
+    }
+}
+
+/// Pixel format written to the output stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputPixelFormat {
+    Yuv420p,
+    Yuv420p10,
+    Yuv422p10,
+    Yuv444p10,
+}
+
+impl OutputPixelFormat {
+    pub fn av_format(&self) -> ffi::AVPixelFormat {
+        match self {
+            OutputPixelFormat::Yuv420p => ffi::AV_PIX_FMT_YUV420P,
+            OutputPixelFormat::Yuv420p10 => ffi::AV_PIX_FMT_YUV420P10LE,
+            OutputPixelFormat::Yuv422p10 => ffi::AV_PIX_FMT_YUV422P10LE,
+            OutputPixelFormat::Yuv444p10 => ffi::AV_PIX_FMT_YUV444P10LE,
+        }
+    }
+
+    pub fn subsampling(&self) -> ChromaSubsampling {
+        match self {
+            OutputPixelFormat::Yuv420p | OutputPixelFormat::Yuv420p10 => ChromaSubsampling::Yuv420,
+            OutputPixelFormat::Yuv422p10 => ChromaSubsampling::Yuv422,
+            OutputPixelFormat::Yuv444p10 => ChromaSubsampling::Yuv444,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RateControl {
+    Cbr(i64), // bits per second, min = max = target
+    Abr(i64), // average bits per second
+    Crf(f32), // constant rate factor, encoder specific scale
+    Qp(u32),  // constant quantizer
+}
+
+impl Default for RateControl {
+    fn default() -> Self {
+        RateControl::Cbr(4_000_000)
+    }
+}
+
+/// User-facing encoder choices. Codec-specific knobs (preset, tune, profile, level,
+/// CRF/QP) are passed as private options, so unknown ones are reported rather than fatal.
+#[derive(Debug, Clone, Default)]
+pub struct EncoderOptions {
+    pub codec: VideoCodec,
+    pub container: Option<String>, // FFmpeg muxer name, None = guess from output extension
+    pub rate_control: RateControl,
+    pub preset: Option<String>,
+    pub tune: Option<String>,
+    pub profile: Option<String>,
+    pub level: Option<String>,
+    pub gop_size: Option<i32>,
+    pub max_b_frames: Option<i32>,
+    pub pixel_format: Option<OutputPixelFormat>, // None = 8-bit or 10-bit 4:2:0 to match the pipeline depth
+    pub private_options: Vec<(String, String)>, // applied last, override the fields above
+}
+
+impl EncoderOptions {
+    /// Chroma subsampling frames should arrive in, so 4:2:2/4:4:4 output is
+    /// converted from RGB directly instead of being upsampled from 4:2:0.
+    pub fn chroma_subsampling(&self) -> ChromaSubsampling {
+        self.pixel_format.map(|f| f.subsampling()).unwrap_or_default()
+    }
+}
 
 pub struct EncoderConfig {
     pub width: i32,
     pub height: i32,
-    pub time_base: ffi::AVRational,
-    pub bitrate: i64,
+    pub time_base: ffi::AVRational,  // input video stream time base, frame pts are rescaled into it
+    pub frame_rate: ffi::AVRational, // average rate, used for frames without timestamps
+    pub color: ColorInfo,            // tags and HDR metadata copied from the input
+    pub sample_aspect_ratio: ffi::AVRational, // of the output pixels, sets the display aspect ratio
+    pub audio: AudioOptions,
+    pub streams: StreamSelection,
+    pub options: EncoderOptions,
 }
 
-pub fn run_encoder(
+// Output side of a passed-through input stream, by output stream index
+enum Track {
+    Copy { stream: usize, in_time_base: ffi::AVRational },
+    Transcode { stream: usize, transcoder: AudioTranscoder },
+}
+
+pub fn run_encoder<T: Sample>(
     input_path: &str, // Needed to copy audio params
     output_path: &str,
-    rx_encoder: Receiver<EncoderMsg>,
+    rx_encoder: Receiver<EncoderMsg<T>>,
     config: EncoderConfig,
+    progress: ProgressCounters,
 ) -> Result<()> {
-    unsafe {
-        let out_str_c = CString::new(output_path).unwrap();
-        let in_str = input_path.to_string();
-        
-        let mut safe_out_ctx = crate::video::wrappers::SafeFormatContextOutput::new();
-        ffi::avformat_alloc_output_context2(&mut safe_out_ctx.ptr, ptr::null_mut(), ptr::null_mut(), out_str_c.as_ptr());
-        
-        if safe_out_ctx.ptr.is_null() {
-            return Err(anyhow!("Could not allocate output context"));
-        }
+    let opts = &config.options;
+    let mut out_ctx = SafeFormatContextOutput::create(output_path, opts.container.as_deref())?;
+
+    // Setup Video Stream
+    let encoder = find_encoder(opts.codec.encoder_name(), opts.codec.codec_id())
+        .ok_or_else(|| anyhow!("No encoder available for {:?}", opts.codec))?;
+    let out_video_stream = out_ctx.add_stream()?;
 
-        // Setup Video Stream
-        let encoder = ffi::avcodec_find_encoder(ffi::AV_CODEC_ID_H264);
-        let out_video_stream = ffi::avformat_new_stream(safe_out_ctx.ptr, ptr::null());
-        
-        let mut safe_encode_ctx = crate::video::wrappers::SafeCodecContext::new(encoder);
-        (*safe_encode_ctx.ptr).width = config.width;
-        (*safe_encode_ctx.ptr).height = config.height;
-        (*safe_encode_ctx.ptr).time_base = config.time_base; 
-        (*safe_encode_ctx.ptr).pix_fmt = ffi::AV_PIX_FMT_YUV420P;
-        (*safe_encode_ctx.ptr).bit_rate = config.bitrate;
-        (*safe_encode_ctx.ptr).rc_min_rate = config.bitrate;
-        (*safe_encode_ctx.ptr).rc_max_rate = config.bitrate;
-        (*safe_encode_ctx.ptr).rc_buffer_size = (config.bitrate / 2) as i32;
-
-        ffi::avcodec_parameters_from_context((*out_video_stream).codecpar, safe_encode_ctx.ptr);
-        if ffi::avcodec_open2(safe_encode_ctx.ptr, encoder, ptr::null_mut()) < 0 {
-             return Err(anyhow!("Failed to open video encoder"));
+    // Frames arrive as 8- or 16-bit planar YUV at the requested subsampling; anything
+    // else the encoder wants (10-bit, 4:2:2 for ProRes, ...) is produced by a conversion pass
+    let subsampling = opts.chroma_subsampling();
+    let frame_format = T::yuv_format(subsampling);
+    let preferred_pix_fmt = match opts.pixel_format {
+        Some(fmt) => fmt.av_format(),
+        None if std::mem::size_of::<T>() > 1 => ffi::AV_PIX_FMT_YUV420P10LE,
+        None => ffi::AV_PIX_FMT_YUV420P,
+    };
+    let enc_pix_fmt = pick_pix_fmt(encoder, preferred_pix_fmt);
+
+    let mut encode_ctx = SafeCodecContext::new(encoder)?;
+    encode_ctx.width = config.width;
+    encode_ctx.height = config.height;
+    encode_ctx.time_base = config.time_base;
+    encode_ctx.framerate = config.frame_rate;
+    encode_ctx.pix_fmt = enc_pix_fmt;
+    encode_ctx.sample_aspect_ratio = config.sample_aspect_ratio;
+    config.color.apply_to_encoder(&mut encode_ctx);
+    if let Some(gop) = opts.gop_size {
+        encode_ctx.gop_size = gop;
+    }
+    if let Some(b_frames) = opts.max_b_frames {
+        encode_ctx.max_b_frames = b_frames;
+    }
+
+    let mut enc_opts = SafeDictionary::new();
+    match opts.rate_control {
+        RateControl::Cbr(bitrate) => {
+            encode_ctx.bit_rate = bitrate;
+            encode_ctx.rc_min_rate = bitrate;
+            encode_ctx.rc_max_rate = bitrate;
+            encode_ctx.rc_buffer_size = (bitrate / 2) as i32;
+        }
+        RateControl::Abr(bitrate) => {
+            encode_ctx.bit_rate = bitrate;
+        }
+        RateControl::Crf(crf) => {
+            encode_ctx.bit_rate = 0;
+            enc_opts.set("crf", &crf.to_string());
         }
+        RateControl::Qp(qp) => {
+            encode_ctx.bit_rate = 0;
+            enc_opts.set("qp", &qp.to_string());
+        }
+    }
+    for (key, value) in [("preset", &opts.preset), ("tune", &opts.tune), ("profile", &opts.profile), ("level", &opts.level)] {
+        if let Some(value) = value {
+            enc_opts.set(key, value);
+        }
+    }
+    // Frames forced to I at scene cuts should be IDR frames, so players can seek to them
+    if matches!(codec_name(encoder).as_str(), "libx264" | "libx265") {
+        enc_opts.set("forced-idr", "1");
+    }
+    for (key, value) in &opts.private_options {
+        enc_opts.set(key, value);
+    }
+
+    let global_header = out_ctx.needs_global_header();
+    if global_header {
+        encode_ctx.flags |= ffi::AV_CODEC_FLAG_GLOBAL_HEADER as i32;
+    }
+
+    encode_ctx.open(&mut enc_opts)
+        .with_context(|| format!("Failed to open video encoder {}", opts.codec.encoder_name()))?;
+    for key in enc_opts.keys() {
+        eprintln!("⚠️ Encoder option '{}' was not recognized and has been ignored", key);
+    }
+    {
+        let stream = out_ctx.stream_mut(out_video_stream).ok_or_else(|| anyhow!("Output video stream missing"))?;
+        // Copy params after open so extradata (SPS/PPS, hvcC, av1C...) reaches the muxer
+        encode_ctx.copy_params_to(stream)?;
+        config.color.apply_to_stream(codecpar_mut(stream));
+        stream.time_base = config.time_base; // Muxer hint, may be adjusted by write_header
+        stream.sample_aspect_ratio = config.sample_aspect_ratio;
+    }
+
+    // Map the other input streams (copied, or re-encoded for audio), chapters and metadata
+    // Note: We use a temporary Safe input context just to read params, it will auto-close!
+    let mut tracks: Vec<Option<Track>> = Vec::new(); // indexed by input stream
+    {
+        let in_ctx = SafeFormatContextInput::open(input_path)
+            .with_context(|| format!("Could not reopen {} to map its streams", input_path))
+            .map_err(XStreamError::Decode)?;
+
+        streams::copy_container_props(&mut out_ctx, &in_ctx);
+        let video_stream_idx = streams::find_video_stream(&in_ctx);
+        let format_name = out_ctx.format_name();
+
+        for (i, s) in in_ctx.streams().enumerate() {
+            if Some(i) == video_stream_idx {
+                if let Some(out) = out_ctx.stream_mut(out_video_stream) {
+                    streams::copy_stream_props(out, s);
+                }
+                tracks.push(None);
+                continue;
+            }
+            if !config.streams.selects(i, s) {
+                tracks.push(None);
+                continue;
+            }
+
+            let par = codecpar(s);
+            let target = if par.codec_type == ffi::AVMEDIA_TYPE_AUDIO {
+                config.audio.transcode_target(&out_ctx, par.codec_id)
+            } else {
+                None
+            };
+            if target.is_none() && out_ctx.accepts_codec(par.codec_id) == Some(false) {
+                eprintln!("⚠️ Stream #{} cannot be stored in {} and has been skipped", i, format_name);
+                tracks.push(None);
+                continue;
+            }
 
-        // Setup Audio Stream (Copy parms from input)
-        // Note: We use a temporary Safe input context just to read params, it will auto-close!
-        let mut out_audio_stream: *mut ffi::AVStream = ptr::null_mut();
-        let mut input_audio_tb = ffi::AVRational{num:1,den:1};
-        {
-             let in_c_temp = CString::new(in_str).unwrap();
-             let mut safe_temp_fmt = crate::video::wrappers::SafeFormatContextInput::new();
-             ffi::avformat_open_input(&mut safe_temp_fmt.ptr, in_c_temp.as_ptr(), ptr::null_mut(), ptr::null_mut());
-             ffi::avformat_find_stream_info(safe_temp_fmt.ptr, ptr::null_mut());
-             
-             let mut audio_stream_idx = -1;
-             for i in 0..(*safe_temp_fmt.ptr).nb_streams {
-                  if (*(*(*(*safe_temp_fmt.ptr).streams.add(i as usize))).codecpar).codec_type == ffi::AVMEDIA_TYPE_AUDIO {
-                      audio_stream_idx = i as i32;
-                      break;
-                  }
-             }
-             
-             if audio_stream_idx != -1 {
-                  let s = *(*safe_temp_fmt.ptr).streams.add(audio_stream_idx as usize);
-                  out_audio_stream = ffi::avformat_new_stream(safe_out_ctx.ptr, ptr::null());
-                  ffi::avcodec_parameters_copy((*out_audio_stream).codecpar, (*s).codecpar);
-                  (*(*out_audio_stream).codecpar).codec_tag = 0;
-                  input_audio_tb = (*s).time_base;
-             }
-        } // safe_temp_fmt dropped here, closed safe.
-
-
-        if ((*(*safe_out_ctx.ptr).oformat).flags as i32 & ffi::AVFMT_NOFILE as i32) == 0 {
-            ffi::avio_open(&mut (*safe_out_ctx.ptr).pb, out_str_c.as_ptr(), ffi::AVIO_FLAG_WRITE as i32);
+            let out_index = out_ctx.add_stream()
+                .with_context(|| format!("Could not add stream #{} to the output", i))?;
+            let out_stream = out_ctx.stream_mut(out_index).ok_or_else(|| anyhow!("Output stream #{} missing", out_index))?;
+            streams::copy_stream_props(out_stream, s);
+            match target {
+                Some(codec) => {
+                    let transcoder = AudioTranscoder::new(par, s.time_base, codec, &config.audio, global_header)?;
+                    transcoder.setup_stream(out_stream)?;
+                    println!("🔊 Audio #{}: re-encoding to {:?}", i, codec);
+                    tracks.push(Some(Track::Transcode { stream: out_index, transcoder }));
+                }
+                None => {
+                    copy_codecpar(out_stream, s)?;
+                    codecpar_mut(out_stream).codec_tag = 0;
+                    out_stream.time_base = s.time_base; // Muxer hint
+                    tracks.push(Some(Track::Copy { stream: out_index, in_time_base: s.time_base }));
+                }
+            }
         }
-        ffi::avformat_write_header(safe_out_ctx.ptr, ptr::null_mut());
-
-        // Resources for Encoder
-        let mut safe_out_frame = crate::video::wrappers::SafeFrame::new();
-        (*safe_out_frame.ptr).width = config.width;
-        (*safe_out_frame.ptr).height = config.height;
-        (*safe_out_frame.ptr).format = ffi::AV_PIX_FMT_YUV420P as i32;
-        ffi::av_frame_get_buffer(safe_out_frame.ptr, 32);
-        
-        let mut safe_pkt = crate::video::wrappers::SafePacket::new();
-
-        let mut video_frames_done = 0;
-        let mut total_processed: i64 = 0;
-
-        loop {
-            match rx_encoder.recv() {
-                Ok(EncoderMsg::Video(up_frame)) => {
-                    let w = config.width;
-                    let h = config.height;
-                    let y_size = (w * h) as usize;
-                    let u_size = (w/2 * h/2) as usize;
-                    let v_size = u_size;
-                    
-                    if up_frame.data.len() >= y_size + u_size + v_size {
-                         // Copy Y
-                         let src_y = &up_frame.data[0..y_size];
-                         for i in 0..h {
-                             let src_start = (i * w) as usize;
-                             let dst_start = (i * (*safe_out_frame.ptr).linesize[0]) as usize;
-                             ptr::copy_nonoverlapping(src_y[src_start..].as_ptr(), 
-                                                     (*safe_out_frame.ptr).data[0].add(dst_start), 
-                                                     w as usize);
-                         }
-                         
-                         // Copy U
-                         let src_u = &up_frame.data[y_size .. y_size+u_size];
-                         for i in 0..h/2 {
-                             let src_start = (i * w/2) as usize;
-                             let dst_start = (i * (*safe_out_frame.ptr).linesize[1]) as usize;
-                              ptr::copy_nonoverlapping(src_u[src_start..].as_ptr(), 
-                                                     (*safe_out_frame.ptr).data[1].add(dst_start), 
-                                                     (w/2) as usize);
-                         }
-
-                         // Copy V
-                         let src_v = &up_frame.data[y_size+u_size .. y_size+u_size+v_size];
-                         for i in 0..h/2 {
-                             let src_start = (i * w/2) as usize;
-                             let dst_start = (i * (*safe_out_frame.ptr).linesize[2]) as usize;
-                              ptr::copy_nonoverlapping(src_v[src_start..].as_ptr(), 
-                                                     (*safe_out_frame.ptr).data[2].add(dst_start), 
-                                                     (w/2) as usize);
-                         }
-                    }
-                    
-                    (*safe_out_frame.ptr).pts = video_frames_done; 
-                    video_frames_done += 1;
-                    total_processed += 1;
-
-                    if total_processed % 10 == 0 {
-                         print!("\r🚀 Processing Frame: {}", total_processed);
-                         std::io::stdout().flush().ok();
+    } // in_ctx dropped here, closed safe.
+
+    out_ctx.write_header(output_path)?;
+
+    // Resources for Encoder
+    let mut out_frame = SafeFrame::video(config.width, config.height, frame_format)?;
+    config.color.apply_to_frame(&mut out_frame);
+
+    let mut conversion = None;
+    if enc_pix_fmt != frame_format {
+        let mut conv_frame = SafeFrame::video(config.width, config.height, enc_pix_fmt)?;
+        config.color.apply_to_frame(&mut conv_frame);
+        let mut sws = SafeSwsContext::new(
+            config.width, config.height, frame_format,
+            config.width, config.height, enc_pix_fmt,
+            ffi::SWS_BICUBIC as i32
+        ).context("Could not create the encoder's pixel format converter")?;
+        // Same matrix and range on both sides: only depth and subsampling change
+        let space = config.color.sws_colorspace();
+        let full_range = config.color.is_full_range();
+        sws.set_colorspace(space, full_range, space, full_range)?;
+        conversion = Some((sws, conv_frame));
+    }
+
+    let mut pkt = SafePacket::new()?;
+
+    let enc_time_base = encode_ctx.time_base;
+    // Nominal frame length in encoder ticks, for frames that arrive without pts/duration
+    let frame_duration = if config.frame_rate.num > 0 && config.frame_rate.den > 0 {
+        let frame_rate = config.frame_rate;
+        rescale_q(1, ffi::AVRational { num: frame_rate.den, den: frame_rate.num }, enc_time_base).max(1)
+    } else {
+        1
+    };
+    let planes = subsampling.plane_sizes(config.width, config.height);
+    let mut last_pts = ffi::AV_NOPTS_VALUE;
+
+    loop {
+        match rx_encoder.recv() {
+            Ok(EncoderMsg::Video(up_frame)) => {
+                // The encoder may still reference the previous frame's buffers
+                out_frame.make_writable()?;
+                // Copy Y, U and V row by row into the frame's padded lines
+                out_frame.write_planes(&up_frame.data, &planes)?;
+
+                // Keep the source timing (VFR-safe), only patching gaps and non-increasing pts
+                let mut pts = if up_frame.pts == ffi::AV_NOPTS_VALUE {
+                    if last_pts == ffi::AV_NOPTS_VALUE { 0 } else { last_pts + frame_duration }
+                } else {
+                    rescale_q(up_frame.pts, up_frame.time_base, enc_time_base)
+                };
+                if last_pts != ffi::AV_NOPTS_VALUE && pts <= last_pts {
+                    pts = last_pts + 1;
+                }
+                last_pts = pts;
+
+                let send_frame = match &mut conversion {
+                    Some((sws, conv_frame)) => {
+                        conv_frame.make_writable()?;
+                        sws.scale_frame(&out_frame, conv_frame)
+                            .context("Conversion to the encoder pixel format failed")?;
+                        conv_frame
                     }
+                    None => &mut out_frame,
+                };
 
-                    if ffi::avcodec_send_frame(safe_encode_ctx.ptr, safe_out_frame.ptr) >= 0 {
-                        while ffi::avcodec_receive_packet(safe_encode_ctx.ptr, safe_pkt.ptr) == 0 {
-                            ffi::av_packet_rescale_ts(safe_pkt.ptr, (*safe_encode_ctx.ptr).time_base, (*out_video_stream).time_base);
-                            (*safe_pkt.ptr).stream_index = (*out_video_stream).index;
-                            ffi::av_interleaved_write_frame(safe_out_ctx.ptr, safe_pkt.ptr);
-                            ffi::av_packet_unref(safe_pkt.ptr);
-                        }
+                send_frame.pts = pts;
+                send_frame.pict_type = if up_frame.scene_cut { ffi::AV_PICTURE_TYPE_I } else { ffi::AV_PICTURE_TYPE_NONE };
+                send_frame.duration = if up_frame.duration > 0 {
+                    rescale_q(up_frame.duration, up_frame.time_base, enc_time_base)
+                } else {
+                    frame_duration
+                };
+
+                encode_ctx.send_frame(Some(send_frame))?;
+                write_encoded(&mut encode_ctx, &mut out_ctx, out_video_stream, &mut pkt)?;
+                progress.add_encoded();
+                progress.set_output_bytes(out_ctx.bytes_written());
+            },
+            Ok(EncoderMsg::Packet(packet_data)) => {
+                match tracks.get_mut(packet_data.stream_index as usize) {
+                    Some(Some(Track::Transcode { stream, transcoder })) => {
+                        transcoder.transcode(&packet_data, &mut out_ctx, *stream)?;
+                    }
+                    Some(Some(Track::Copy { stream, in_time_base })) => {
+                        let out_time_base = out_ctx.stream(*stream).map(|s| s.time_base)
+                            .ok_or_else(|| anyhow!("Output stream #{} missing", stream))?;
+                        let mut new_pkt = SafePacket::from_data(&packet_data)?;
+                        new_pkt.rescale_ts(*in_time_base, out_time_base);
+                        new_pkt.stream_index = *stream as i32;
+                        out_ctx.write_packet(&mut new_pkt)?;
                     }
-                },
-                Ok(EncoderMsg::Audio(packet_data)) => {
-                    if !out_audio_stream.is_null() {
-                         let mut safe_new_pkt = crate::video::wrappers::SafePacket::new();
-                         ffi::av_new_packet(safe_new_pkt.ptr, packet_data.data.len() as i32);
-                         ptr::copy_nonoverlapping(packet_data.data.as_ptr(), (*safe_new_pkt.ptr).data, packet_data.data.len());
-                         (*safe_new_pkt.ptr).pts = packet_data.pts;
-                         (*safe_new_pkt.ptr).dts = packet_data.dts;
-                         (*safe_new_pkt.ptr).duration = packet_data.duration;
-                         (*safe_new_pkt.ptr).flags = packet_data.flags;
-                         
-                         ffi::av_packet_rescale_ts(safe_new_pkt.ptr, input_audio_tb, (*out_audio_stream).time_base);
-                         (*safe_new_pkt.ptr).stream_index = (*out_audio_stream).index;
-                         ffi::av_interleaved_write_frame(safe_out_ctx.ptr, safe_new_pkt.ptr);
+                    _ => {} // Stream not mapped to the output
+                }
+            },
+            Ok(EncoderMsg::EOF) | Err(_) => {
+                // Flush
+                encode_ctx.send_frame(None).context("Video encoder flush failed")?;
+                write_encoded(&mut encode_ctx, &mut out_ctx, out_video_stream, &mut pkt)?;
+                for track in tracks.iter_mut().flatten() {
+                    if let Track::Transcode { stream, transcoder } = track {
+                        transcoder.flush(&mut out_ctx, *stream)?;
                     }
-                },
-                Ok(EncoderMsg::EOF) | Err(_) => {
-                    // Flush
-                    ffi::avcodec_send_frame(safe_encode_ctx.ptr, ptr::null());
-                     while ffi::avcodec_receive_packet(safe_encode_ctx.ptr, safe_pkt.ptr) == 0 {
-                            ffi::av_packet_rescale_ts(safe_pkt.ptr, (*safe_encode_ctx.ptr).time_base, (*out_video_stream).time_base);
-                            (*safe_pkt.ptr).stream_index = (*out_video_stream).index;
-                            ffi::av_interleaved_write_frame(safe_out_ctx.ptr, safe_pkt.ptr);
-                            ffi::av_packet_unref(safe_pkt.ptr);
-                     }
-                    break;
                 }
+                break;
             }
         }
+    }
+
+    out_ctx.write_trailer()
+        .with_context(|| format!("Could not finish {}", output_path))?;
+    progress.set_output_bytes(out_ctx.bytes_written());
 
-        ffi::av_write_trailer(safe_out_ctx.ptr);
-        
-        // Manual cleanup removed! Drop traits handle:
-        // - safe_encode_ctx (avcodec_free_context)
-        // - safe_out_ctx (avio_closep if needed + avformat_free_context)
-        // - safe_out_frame
-        // - safe_pkt
+    // Manual cleanup removed! Drop traits handle:
+    // - encode_ctx (avcodec_free_context)
+    // - out_ctx (avio_closep if needed + avformat_free_context)
+    // - out_frame
+    // - pkt
+    Ok(())
+}
+
+// Drains every packet the video encoder has ready into the output
+fn write_encoded(
+    encode_ctx: &mut SafeCodecContext,
+    out_ctx: &mut SafeFormatContextOutput,
+    stream: usize,
+    pkt: &mut SafePacket,
+) -> Result<()> {
+    let out_time_base = out_ctx.stream(stream).map(|s| s.time_base)
+        .ok_or_else(|| anyhow!("Output stream #{} missing", stream))?;
+    while encode_ctx.receive_packet(pkt)? {
+        pkt.rescale_ts(encode_ctx.time_base, out_time_base);
+        pkt.stream_index = stream as i32;
+        // Takes ownership of the packet's data and leaves it blank
+        out_ctx.write_packet(pkt).context("Could not write a video packet")?;
     }
     Ok(())
 }
+
+// Returns `preferred` if the encoder accepts it, otherwise the encoder's first supported format
+fn pick_pix_fmt(codec: &ffi::AVCodec, preferred: ffi::AVPixelFormat) -> ffi::AVPixelFormat {
+    let list = codec.pix_fmts;
+    if list.is_null() {
+        return preferred;
+    }
+    unsafe {
+        let mut p = list;
+        while *p != ffi::AV_PIX_FMT_NONE {
+            if *p == preferred {
+                return preferred;
+            }
+            p = p.add(1);
+        }
+        if *list == ffi::AV_PIX_FMT_NONE { preferred } else { *list }
+    }
+}
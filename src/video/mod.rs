@@ -1,4 +1,12 @@
+pub mod audio;
+pub mod averror;
+pub mod color;
+pub mod convert;
 pub mod decoder;
 pub mod encoder;
+pub mod scale;
+pub mod scene;
+pub mod stabilize;
+pub mod streams;
 pub mod types;
 pub mod wrappers;
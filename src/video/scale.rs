@@ -0,0 +1,222 @@
+// src/video/scale.rs
+
+use rsmpeg::ffi;
+use anyhow::{Result, anyhow};
+use crate::video::types::{RawFrame, Sample};
+
+/// How the source picture is mapped onto the output frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleMode {
+    /// Exactly `width`x`height`, ignoring the source aspect ratio.
+    Stretch { width: u32, height: u32 },
+    /// Largest size that fits in `width`x`height`, padded with black bars.
+    Fit { width: u32, height: u32 },
+    /// Covers `width`x`height`, cropping whatever overflows.
+    Fill { width: u32, height: u32 },
+    /// Source size times the factor (x2, x4, ...), keeping the source sample aspect ratio.
+    Factor(u32),
+    /// Fixed width, height following the source display aspect ratio.
+    Width(u32),
+}
+
+impl Default for ScaleMode {
+    fn default() -> Self {
+        ScaleMode::Fit { width: 1920, height: 1080 }
+    }
+}
+
+/// Rectangle in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Frame geometry resolved from a `ScaleMode` for a given source.
+#[derive(Debug, Clone, Copy)]
+pub struct ScalePlan {
+    pub crop: Rect,    // part of the source frame that is kept
+    pub content: Rect, // where the scaled crop lands in the output frame
+    pub output: (u32, u32),
+    pub sample_aspect_ratio: ffi::AVRational, // of the output pixels
+}
+
+impl ScaleMode {
+    /// Works out crop, placement and output size for a `src_w`x`src_h` source with
+    /// sample aspect ratio `sar` (0/1 when unknown = square pixels). Output sizes
+    /// are rounded to even numbers so every chroma subsampling can encode them.
+    pub fn plan(&self, src_w: u32, src_h: u32, sar: ffi::AVRational) -> Result<ScalePlan> {
+        if src_w == 0 || src_h == 0 {
+            return Err(anyhow!("Source has no picture size"));
+        }
+        let sar_known = sar.num > 0 && sar.den > 0;
+        let sar_f = if sar_known { sar.num as f64 / sar.den as f64 } else { 1.0 };
+        // Source size in square display pixels
+        let (dw, dh) = (src_w as f64 * sar_f, src_h as f64);
+
+        let square = ffi::AVRational { num: 1, den: 1 };
+        let full_crop = Rect { x: 0, y: 0, width: src_w, height: src_h };
+        let whole = |(w, h): (u32, u32)| Rect { x: 0, y: 0, width: w, height: h };
+
+        let plan = match *self {
+            ScaleMode::Stretch { width, height } => {
+                let output = (even(width as f64), even(height as f64));
+                ScalePlan { crop: full_crop, content: whole(output), output, sample_aspect_ratio: square }
+            }
+            ScaleMode::Factor(factor) => {
+                if factor == 0 {
+                    return Err(anyhow!("Scale factor must be at least 1"));
+                }
+                let output = (even((src_w * factor) as f64), even((src_h * factor) as f64));
+                let sample_aspect_ratio = if sar_known { sar } else { square };
+                ScalePlan { crop: full_crop, content: whole(output), output, sample_aspect_ratio }
+            }
+            ScaleMode::Width(width) => {
+                let output = (even(width as f64), even(width as f64 * dh / dw));
+                ScalePlan { crop: full_crop, content: whole(output), output, sample_aspect_ratio: square }
+            }
+            ScaleMode::Fit { width, height } => {
+                let output = (even(width as f64), even(height as f64));
+                let s = (output.0 as f64 / dw).min(output.1 as f64 / dh);
+                let cw = even(dw * s).min(output.0);
+                let ch = even(dh * s).min(output.1);
+                let content = Rect {
+                    x: ((output.0 - cw) / 2) & !1,
+                    y: ((output.1 - ch) / 2) & !1,
+                    width: cw,
+                    height: ch,
+                };
+                ScalePlan { crop: full_crop, content, output, sample_aspect_ratio: square }
+            }
+            ScaleMode::Fill { width, height } => {
+                let output = (even(width as f64), even(height as f64));
+                let s = (output.0 as f64 / dw).max(output.1 as f64 / dh);
+                let crop_w = ((output.0 as f64 / s / sar_f).round() as u32).clamp(1, src_w);
+                let crop_h = ((output.1 as f64 / s).round() as u32).clamp(1, src_h);
+                let crop = Rect {
+                    x: (src_w - crop_w) / 2,
+                    y: (src_h - crop_h) / 2,
+                    width: crop_w,
+                    height: crop_h,
+                };
+                ScalePlan { crop, content: whole(output), output, sample_aspect_ratio: square }
+            }
+        };
+        Ok(plan)
+    }
+}
+
+impl ScalePlan {
+    /// Cuts the kept region out of a decoded frame; frames that need no crop pass through.
+    pub fn crop_frame<T: Sample>(&self, frame: RawFrame<T>) -> RawFrame<T> {
+        let c = self.crop;
+        if (c.width as i32, c.height as i32) == (frame.width, frame.height) {
+            return frame;
+        }
+        let src_w = frame.width as usize;
+        let mut data = Vec::with_capacity((c.width * c.height * 3) as usize);
+        for row in c.y..c.y + c.height {
+            let start = (row as usize * src_w + c.x as usize) * 3;
+            data.extend_from_slice(&frame.data[start..start + c.width as usize * 3]);
+        }
+        RawFrame { data, width: c.width as i32, height: c.height as i32, ..frame }
+    }
+
+    /// Places interleaved content of `content` size into a black output frame.
+    pub fn pad<T: Sample>(&self, content: Vec<T>, channels: usize) -> Vec<T> {
+        let c = self.content;
+        let (ow, oh) = self.output;
+        if (c.width, c.height) == (ow, oh) {
+            return content;
+        }
+        let row_len = c.width as usize * channels;
+        let mut out = vec![T::default(); ow as usize * oh as usize * channels];
+        for (row, src) in content.chunks_exact(row_len).enumerate() {
+            let start = ((c.y as usize + row) * ow as usize + c.x as usize) * channels;
+            out[start..start + row_len].copy_from_slice(src);
+        }
+        out
+    }
+}
+
+// Nearest even size, at least 2
+fn even(v: f64) -> u32 {
+    (((v / 2.0).round() as u32) * 2).max(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNKNOWN: ffi::AVRational = ffi::AVRational { num: 0, den: 1 };
+
+    fn rect(x: u32, y: u32, width: u32, height: u32) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    #[test]
+    fn stretch_and_factor_keep_the_whole_frame() {
+        let plan = ScaleMode::Stretch { width: 1280, height: 720 }.plan(640, 480, UNKNOWN).unwrap();
+        assert_eq!((plan.output, plan.crop, plan.content), ((1280, 720), rect(0, 0, 640, 480), rect(0, 0, 1280, 720)));
+
+        let plan = ScaleMode::Factor(2).plan(640, 360, UNKNOWN).unwrap();
+        assert_eq!(plan.output, (1280, 720));
+        assert_eq!((plan.sample_aspect_ratio.num, plan.sample_aspect_ratio.den), (1, 1));
+        // Anamorphic sources keep their pixel shape
+        let sar = ffi::AVRational { num: 4, den: 3 };
+        let plan = ScaleMode::Factor(2).plan(1440, 1080, sar).unwrap();
+        assert_eq!((plan.output, plan.sample_aspect_ratio.num, plan.sample_aspect_ratio.den), ((2880, 2160), 4, 3));
+    }
+
+    #[test]
+    fn width_follows_the_display_aspect_ratio() {
+        // PAL 4:3 with 16:15 pixels is 768x576 on screen
+        let plan = ScaleMode::Width(1280).plan(720, 576, ffi::AVRational { num: 16, den: 15 }).unwrap();
+        assert_eq!(plan.output, (1280, 960));
+    }
+
+    #[test]
+    fn fit_pads_and_fill_crops() {
+        let plan = ScaleMode::Fit { width: 1920, height: 1080 }.plan(640, 480, UNKNOWN).unwrap();
+        assert_eq!((plan.output, plan.crop, plan.content), ((1920, 1080), rect(0, 0, 640, 480), rect(240, 0, 1440, 1080)));
+
+        let plan = ScaleMode::Fill { width: 1920, height: 1080 }.plan(640, 480, UNKNOWN).unwrap();
+        assert_eq!((plan.output, plan.crop, plan.content), ((1920, 1080), rect(0, 60, 640, 360), rect(0, 0, 1920, 1080)));
+    }
+
+    #[test]
+    fn output_sizes_are_even() {
+        let plan = ScaleMode::Stretch { width: 1279, height: 1 }.plan(640, 480, UNKNOWN).unwrap();
+        assert_eq!(plan.output, (1280, 2));
+        let plan = ScaleMode::Width(1001).plan(333, 333, UNKNOWN).unwrap();
+        assert_eq!(plan.output, (1002, 1002));
+    }
+
+    #[test]
+    fn bad_sizes_are_rejected() {
+        assert!(ScaleMode::default().plan(0, 480, UNKNOWN).is_err());
+        assert!(ScaleMode::Factor(0).plan(640, 480, UNKNOWN).is_err());
+    }
+
+    #[test]
+    fn crop_and_pad_move_pixels() {
+        let plan = ScalePlan { crop: rect(1, 0, 2, 2), content: rect(2, 1, 2, 1), output: (4, 2), sample_aspect_ratio: UNKNOWN };
+        // 4x2 RGB frame whose pixels hold their index
+        let frame = RawFrame {
+            data: (0u8..8).flat_map(|p| [p; 3]).collect(),
+            width: 4,
+            height: 2,
+            pts: 0,
+            duration: 1,
+            time_base: ffi::AVRational { num: 1, den: 25 },
+            scene_cut: false,
+        };
+        let cropped = plan.crop_frame(frame);
+        assert_eq!((cropped.width, cropped.height), (2, 2));
+        assert_eq!(cropped.data, [1, 2, 5, 6].iter().flat_map(|&p| [p; 3]).collect::<Vec<u8>>());
+
+        assert_eq!(plan.pad(vec![7u8, 9], 1), vec![0, 0, 0, 0, 0, 0, 7, 9]);
+    }
+}
@@ -0,0 +1,203 @@
+// src/video/scene.rs
+
+use std::fmt::Write as _;
+use rsmpeg::ffi;
+use crate::video::types::{RawFrame, Sample};
+
+/// Cut score above which a frame starts a new scene, see `SceneDetector`.
+pub const DEFAULT_THRESHOLD: f32 = 0.35;
+
+/// Luma samples per row and column compared between frames.
+const GRID: usize = 64;
+/// Bins of the luma histogram.
+const BINS: usize = 32;
+/// Mean absolute luma difference (0-1) that counts as a complete change.
+const FULL_CHANGE: f32 = 0.25;
+
+/// Scene-cut detection settings.
+#[derive(Debug, Clone)]
+pub struct SceneDetection {
+    pub threshold: f32,          // cut score (0-1) from which a frame starts a new scene
+    pub sidecar: Option<String>, // cut list written here at the end, EDL for *.edl, JSON otherwise
+}
+
+impl Default for SceneDetection {
+    fn default() -> Self {
+        Self { threshold: DEFAULT_THRESHOLD, sidecar: None }
+    }
+}
+
+/// First frame of a new scene.
+#[derive(Debug, Clone, Copy)]
+pub struct SceneCut {
+    pub frame: u64, // index in decode order
+    pub pts: i64,   // in `time_base` units, AV_NOPTS_VALUE if unknown
+    pub time_base: ffi::AVRational,
+}
+
+/// Finds scene cuts in decoded frames. Each frame is sampled on a coarse luma grid and
+/// compared with the previous one twice: by luma histogram, which ignores motion, and
+/// by mean absolute difference, which catches cuts between similarly lit shots.
+/// The cut score is the average of both, each 0-1.
+pub struct SceneDetector {
+    threshold: f32,
+    previous: Option<(Vec<f32>, Vec<f32>)>, // luma grid and histogram of the last frame
+    cuts: Vec<SceneCut>,
+}
+
+impl SceneDetector {
+    pub fn new(threshold: f32) -> Self {
+        Self { threshold, previous: None, cuts: Vec::new() }
+    }
+
+    /// True if frame `index` starts a new scene; the cut is remembered. The first
+    /// frame of the stream is not a cut.
+    pub fn check<T: Sample>(&mut self, index: u64, frame: &RawFrame<T>) -> bool {
+        let grid = luma_grid(frame);
+        let histogram = histogram(&grid);
+        let cut = match &self.previous {
+            Some((prev_grid, prev_histogram)) => {
+                let hist_distance = 0.5 * histogram.iter().zip(prev_histogram).map(|(a, b)| (a - b).abs()).sum::<f32>();
+                let mad = grid.iter().zip(prev_grid).map(|(a, b)| (a - b).abs()).sum::<f32>() / grid.len().max(1) as f32;
+                let score = 0.5 * (hist_distance + (mad / FULL_CHANGE).min(1.0));
+                score > self.threshold
+            }
+            None => false,
+        };
+        if cut {
+            self.cuts.push(SceneCut { frame: index, pts: frame.pts, time_base: frame.time_base });
+        }
+        self.previous = Some((grid, histogram));
+        cut
+    }
+
+    pub fn cuts(&self) -> &[SceneCut] {
+        &self.cuts
+    }
+}
+
+// Luma (0-1) at up to GRIDxGRID evenly spaced pixels
+fn luma_grid<T: Sample>(frame: &RawFrame<T>) -> Vec<f32> {
+    let (w, h) = (frame.width.max(0) as usize, frame.height.max(0) as usize);
+    let (gw, gh) = (GRID.min(w), GRID.min(h));
+    let mut grid = Vec::with_capacity(gw * gh);
+    for gy in 0..gh {
+        let y = gy * h / gh;
+        for gx in 0..gw {
+            let i = (y * w + gx * w / gw) * 3;
+            let p = &frame.data[i..i + 3];
+            grid.push(0.299 * p[0].to_unit() + 0.587 * p[1].to_unit() + 0.114 * p[2].to_unit());
+        }
+    }
+    grid
+}
+
+// Normalized BINS-bin histogram of 0-1 values
+fn histogram(values: &[f32]) -> Vec<f32> {
+    let mut bins = vec![0.0; BINS];
+    for &v in values {
+        bins[((v * BINS as f32) as usize).min(BINS - 1)] += 1.0;
+    }
+    let total = values.len().max(1) as f32;
+    bins.iter_mut().for_each(|b| *b /= total);
+    bins
+}
+
+/// Writes `cuts` to `path` for editing tools: a CMX 3600 EDL with one event per scene
+/// if the path ends in `.edl`, JSON with frame indices and times otherwise.
+/// `total_frames` ends the last scene.
+pub fn write_sidecar(path: &str, cuts: &[SceneCut], total_frames: u64, frame_rate: ffi::AVRational) -> std::io::Result<()> {
+    let fps = if frame_rate.num > 0 && frame_rate.den > 0 { frame_rate.num as f64 / frame_rate.den as f64 } else { 25.0 };
+    let contents = if path.to_ascii_lowercase().ends_with(".edl") {
+        edl(cuts, total_frames, fps)
+    } else {
+        json(cuts, fps)
+    };
+    std::fs::write(path, contents)
+}
+
+// Seconds from the start of the stream, from the pts if known
+fn cut_time(cut: &SceneCut, fps: f64) -> f64 {
+    if cut.pts != ffi::AV_NOPTS_VALUE && cut.time_base.den > 0 {
+        cut.pts as f64 * cut.time_base.num as f64 / cut.time_base.den as f64
+    } else {
+        cut.frame as f64 / fps
+    }
+}
+
+fn json(cuts: &[SceneCut], fps: f64) -> String {
+    let mut out = String::from("{\n  \"cuts\": [");
+    for (i, cut) in cuts.iter().enumerate() {
+        let sep = if i == 0 { "" } else { "," };
+        let _ = write!(out, "{}\n    {{ \"frame\": {}, \"time\": {:.6} }}", sep, cut.frame, cut_time(cut, fps));
+    }
+    out.push_str(if cuts.is_empty() { "]\n}\n" } else { "\n  ]\n}\n" });
+    out
+}
+
+// Events cover the scenes between cuts; timecodes count frames at the nominal rate
+fn edl(cuts: &[SceneCut], total_frames: u64, fps: f64) -> String {
+    let tc_rate = (fps.round() as u64).max(1);
+    let timecode = |frame: u64| {
+        let secs = frame / tc_rate;
+        format!("{:02}:{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60, frame % tc_rate)
+    };
+    let mut out = String::from("TITLE: Scene cuts\nFCM: NON-DROP FRAME\n\n");
+    let starts = std::iter::once(0).chain(cuts.iter().map(|c| c.frame));
+    let ends = cuts.iter().map(|c| c.frame).chain(std::iter::once(total_frames));
+    for (event, (start, end)) in starts.zip(ends).filter(|(s, e)| e > s).enumerate() {
+        let (tc_in, tc_out) = (timecode(start), timecode(end));
+        let _ = writeln!(out, "{:03}  AX       V     C        {} {} {} {}", event + 1, tc_in, tc_out, tc_in, tc_out);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIME_BASE: ffi::AVRational = ffi::AVRational { num: 1, den: 1000 };
+
+    // Flat 16x16 frame of one gray level
+    fn gray(level: u8, pts: i64) -> RawFrame<u8> {
+        RawFrame { data: vec![level; 16 * 16 * 3], width: 16, height: 16, pts, duration: 40, time_base: TIME_BASE, scene_cut: false }
+    }
+
+    fn cut(frame: u64, pts: i64) -> SceneCut {
+        SceneCut { frame, pts, time_base: TIME_BASE }
+    }
+
+    #[test]
+    fn detector_flags_hard_changes_only() {
+        let mut detector = SceneDetector::new(DEFAULT_THRESHOLD);
+        assert!(!detector.check(0, &gray(0, 0)), "the first frame is not a cut");
+        assert!(!detector.check(1, &gray(0, 40)));
+        assert!(!detector.check(2, &gray(4, 80)), "a slight brightness change is not a cut");
+        assert!(detector.check(3, &gray(255, 120)));
+        assert!(!detector.check(4, &gray(255, 160)));
+        let cuts: Vec<(u64, i64)> = detector.cuts().iter().map(|c| (c.frame, c.pts)).collect();
+        assert_eq!(cuts, vec![(3, 120)]);
+    }
+
+    #[test]
+    fn json_lists_cuts_with_times() {
+        // Times come from the pts, or from the frame index without one
+        let cuts = [cut(50, 2000), cut(75, ffi::AV_NOPTS_VALUE)];
+        assert_eq!(
+            json(&cuts, 25.0),
+            "{\n  \"cuts\": [\n    { \"frame\": 50, \"time\": 2.000000 },\n    { \"frame\": 75, \"time\": 3.000000 }\n  ]\n}\n"
+        );
+        assert_eq!(json(&[], 25.0), "{\n  \"cuts\": []\n}\n");
+    }
+
+    #[test]
+    fn edl_has_one_event_per_scene() {
+        // A cut on the first frame does not make an empty scene
+        let edl = edl(&[cut(0, 0), cut(50, 2000)], 100, 25.0);
+        let events: Vec<&str> = edl.lines().skip(3).collect();
+        assert_eq!(events, vec![
+            "001  AX       V     C        00:00:00:00 00:00:02:00 00:00:00:00 00:00:02:00",
+            "002  AX       V     C        00:00:02:00 00:00:04:00 00:00:02:00 00:00:04:00",
+        ]);
+    }
+}
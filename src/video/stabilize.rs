@@ -0,0 +1,143 @@
+// src/video/stabilize.rs
+
+use anyhow::Result;
+use crate::processing::FrameProcessor;
+use crate::video::types::{Sample, UpscaledFrame};
+
+/// Side of the square blocks motion is estimated for, in output pixels.
+const BLOCK: usize = 16;
+/// Reach of the motion search between two frames, in output pixels (one short of it).
+const SEARCH_RANGE: isize = 8;
+/// Mean absolute luma difference (0-1 of full scale) of the best match above which a
+/// block counts as really changed rather than shimmering, and is left as it is.
+const CHANGE_THRESHOLD: f32 = 0.03;
+/// Error added per pixel of motion when ranking candidates, so flicker on smooth
+/// gradients is not mistaken for a small shift.
+const MOTION_PENALTY: f32 = 0.001;
+
+/// Reduces frame-to-frame flicker in upscaled output. Each frame's luma is blended with
+/// the previous output, moved by a per-block motion estimate (block matching with a
+/// three-step search). The closer a block matches, the more of the previous frame it
+/// takes, so shimmer on static or steadily moving areas settles while real changes
+/// pass through. Needs the frames in presentation order; starts over at scene cuts.
+pub struct Stabilizer {
+    strength: f32,                              // weight of the previous frame for a perfect match
+    previous: Option<(Vec<f32>, usize, usize)>, // stabilized luma of the last frame with its size
+}
+
+impl Stabilizer {
+    /// `strength` from 0 (off) to 1 (keep the previous frame wherever it matches).
+    pub fn new(strength: f32) -> Self {
+        Self { strength: strength.clamp(0.0, 1.0), previous: None }
+    }
+
+    /// Forgets the previous frame, so the next one passes unchanged.
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+
+    /// Stabilizes a `w`x`h` luma plane (0-1) in place and remembers the result.
+    pub fn stabilize(&mut self, luma: &mut [f32], w: usize, h: usize) {
+        if let Some((prev, pw, ph)) = &self.previous {
+            if (*pw, *ph) == (w, h) {
+                let current = luma.to_vec();
+                for by in (0..h).step_by(BLOCK) {
+                    for bx in (0..w).step_by(BLOCK) {
+                        let block = Block { x: bx, y: by, w: BLOCK.min(w - bx), h: BLOCK.min(h - by) };
+                        let ((dx, dy), error) = block.match_motion(&current, prev, w, h);
+                        let weight = self.strength * (1.0 - error / CHANGE_THRESHOLD).max(0.0);
+                        if weight <= 0.0 {
+                            continue;
+                        }
+                        for y in by..by + block.h {
+                            for x in bx..bx + block.w {
+                                let p = prev[shifted(y, dy, h) * w + shifted(x, dx, w)];
+                                luma[y * w + x] += weight * (p - luma[y * w + x]);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        self.previous = Some((luma.to_vec(), w, h));
+    }
+}
+
+impl<T: Sample> FrameProcessor<UpscaledFrame<T>> for Stabilizer {
+    fn name(&self) -> String {
+        format!("temporal stabilization ({:.2})", self.strength)
+    }
+
+    fn process(&mut self, mut frames: Vec<UpscaledFrame<T>>) -> Result<Vec<UpscaledFrame<T>>> {
+        for frame in &mut frames {
+            if frame.scene_cut {
+                self.reset();
+            }
+            let (w, h) = (frame.width as usize, frame.height as usize);
+            let plane = &mut frame.data[..w * h];
+            let mut luma: Vec<f32> = plane.iter().map(|s| s.to_unit()).collect();
+            self.stabilize(&mut luma, w, h);
+            for (dst, &y) in plane.iter_mut().zip(&luma) {
+                *dst = T::from_unit(y);
+            }
+        }
+        Ok(frames)
+    }
+}
+
+// Coordinate `v + d` clamped into 0..n
+fn shifted(v: usize, d: isize, n: usize) -> usize {
+    (v as isize + d).clamp(0, n as isize - 1) as usize
+}
+
+struct Block {
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+}
+
+impl Block {
+    // Mean absolute difference between this block of `current` and `previous` moved by (dx, dy).
+    // Every other row is compared, which is plenty to rank candidates.
+    fn error(&self, current: &[f32], previous: &[f32], w: usize, h: usize, (dx, dy): (isize, isize)) -> f32 {
+        let (mut sum, mut count) = (0.0, 0);
+        for y in (self.y..self.y + self.h).step_by(2) {
+            let row = shifted(y, dy, h) * w;
+            for x in self.x..self.x + self.w {
+                sum += (current[y * w + x] - previous[row + shifted(x, dx, w)]).abs();
+                count += 1;
+            }
+        }
+        sum / count.max(1) as f32
+    }
+
+    // Three-step search: the best of the eight neighbors at half the search range,
+    // then again around the winner at half the step, down to single pixels.
+    // Returns the motion and the error of its match.
+    fn match_motion(&self, current: &[f32], previous: &[f32], w: usize, h: usize) -> ((isize, isize), f32) {
+        let mut best = (0, 0);
+        let mut best_error = self.error(current, previous, w, h, best);
+        // Close enough to static, the common case for the shimmer this is meant for
+        if best_error <= CHANGE_THRESHOLD / 2.0 {
+            return (best, best_error);
+        }
+        let mut best_cost = best_error;
+        let mut step = SEARCH_RANGE / 2;
+        while step >= 1 {
+            let center = best;
+            for (sx, sy) in [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+                let candidate = (center.0 + sx * step, center.1 + sy * step);
+                let error = self.error(current, previous, w, h, candidate);
+                let cost = error + MOTION_PENALTY * (candidate.0.abs() + candidate.1.abs()) as f32;
+                if cost < best_cost {
+                    best = candidate;
+                    best_error = error;
+                    best_cost = cost;
+                }
+            }
+            step /= 2;
+        }
+        (best, best_error)
+    }
+}
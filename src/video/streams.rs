@@ -0,0 +1,119 @@
+// src/video/streams.rs
+
+use rsmpeg::ffi;
+use std::ffi::{c_void, CStr, CString};
+use std::ptr;
+use crate::video::wrappers::{codecpar, SafeFormatContextInput, SafeFormatContextOutput};
+
+/// Which input streams besides the upscaled video are carried into the output.
+/// Streams the output container cannot hold are skipped with a warning.
+#[derive(Debug, Clone)]
+pub struct StreamSelection {
+    pub audio: bool,
+    pub subtitles: bool,
+    pub data: bool,
+    pub attachments: bool,       // fonts and other files in Matroska
+    pub extra_video: bool,       // cover art and further video streams, copied untouched
+    pub languages: Vec<String>,  // empty = any; untagged streams always pass
+    pub include: Vec<usize>,     // input stream indices, empty = all
+    pub exclude: Vec<usize>,     // input stream indices to drop
+}
+
+impl Default for StreamSelection {
+    fn default() -> Self {
+        Self {
+            audio: true,
+            subtitles: true,
+            data: true,
+            attachments: true,
+            extra_video: true,
+            languages: Vec::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+impl StreamSelection {
+    /// True if input stream `index` should be mapped to the output.
+    pub fn selects(&self, index: usize, stream: &ffi::AVStream) -> bool {
+        if self.exclude.contains(&index) || (!self.include.is_empty() && !self.include.contains(&index)) {
+            return false;
+        }
+        let type_ok = match codecpar(stream).codec_type {
+            ffi::AVMEDIA_TYPE_AUDIO => self.audio,
+            ffi::AVMEDIA_TYPE_SUBTITLE => self.subtitles,
+            ffi::AVMEDIA_TYPE_DATA => self.data,
+            ffi::AVMEDIA_TYPE_ATTACHMENT => self.attachments,
+            ffi::AVMEDIA_TYPE_VIDEO => self.extra_video,
+            _ => false,
+        };
+        if !type_ok {
+            return false;
+        }
+        match (self.languages.is_empty(), stream_language(stream)) {
+            (false, Some(lang)) => self.languages.iter().any(|l| l.eq_ignore_ascii_case(&lang)),
+            _ => true,
+        }
+    }
+}
+
+/// Index of the stream that gets upscaled, picked the same way by probe, decoder and encoder.
+pub fn find_video_stream(ctx: &SafeFormatContextInput) -> Option<usize> {
+    ctx.best_stream(ffi::AVMEDIA_TYPE_VIDEO)
+}
+
+/// The stream's `language` tag, if it has one.
+pub fn stream_language(stream: &ffi::AVStream) -> Option<String> {
+    let key = CString::new("language").unwrap();
+    unsafe {
+        let entry = ffi::av_dict_get(stream.metadata, key.as_ptr(), ptr::null(), 0);
+        if entry.is_null() {
+            None
+        } else {
+            Some(CStr::from_ptr((*entry).value).to_string_lossy().into_owned())
+        }
+    }
+}
+
+/// Copies disposition and tags (language, title, ...) of an input stream to an output
+/// stream. Call before the output header is written.
+pub fn copy_stream_props(out: &mut ffi::AVStream, input: &ffi::AVStream) {
+    out.disposition = input.disposition;
+    unsafe {
+        ffi::av_dict_copy(&mut out.metadata, input.metadata, 0);
+    }
+}
+
+/// Copies container metadata and chapters from the input to the output.
+/// Call before the output header is written.
+pub fn copy_container_props(out_ctx: &mut SafeFormatContextOutput, in_ctx: &SafeFormatContextInput) {
+    unsafe {
+        ffi::av_dict_copy(&mut out_ctx.metadata, in_ctx.metadata, 0);
+
+        for i in 0..in_ctx.nb_chapters as usize {
+            let src = &**in_ctx.chapters.add(i);
+            let dst = ffi::av_mallocz(std::mem::size_of::<ffi::AVChapter>()) as *mut ffi::AVChapter;
+            if dst.is_null() {
+                return;
+            }
+            (*dst).id = src.id;
+            (*dst).time_base = src.time_base;
+            (*dst).start = src.start;
+            (*dst).end = src.end;
+            ffi::av_dict_copy(&mut (*dst).metadata, src.metadata, 0);
+            // Appended like avpriv_new_chapter does, so chapters already on the output are kept;
+            // both the array and its entries are released by avformat_free_context
+            let mut count = out_ctx.nb_chapters as i32;
+            ffi::av_dynarray_add(&mut out_ctx.chapters as *mut _ as *mut c_void, &mut count, dst as *mut c_void);
+            if out_ctx.chapters.is_null() {
+                // On failure av_dynarray_add frees the array, but not the chapter
+                ffi::av_dict_free(&mut (*dst).metadata);
+                ffi::av_free(dst as *mut c_void);
+                out_ctx.nb_chapters = 0;
+                return;
+            }
+            out_ctx.nb_chapters = count as u32;
+        }
+    }
+}
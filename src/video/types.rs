@@ -1,19 +1,143 @@
 // src/video/types.rs
 
+use rsmpeg::ffi;
+use image::{ImageBuffer, Rgb, Luma, imageops};
+use image::imageops::FilterType;
+
+/// Sample type of decoded and upscaled pixel data: `u8` for the classic 8-bit
+/// pipeline, `u16` to carry 10/12/16-bit sources without truncation.
+pub trait Sample: Copy + Default + PartialEq + Send + Sync + std::fmt::Debug + 'static {
+    /// Interleaved RGB format the decoder converts into.
+    const RGB_FORMAT: ffi::AVPixelFormat;
+    /// Planar YUV formats of `UpscaledFrame` data, per chroma subsampling.
+    const YUV420_FORMAT: ffi::AVPixelFormat;
+    const YUV422_FORMAT: ffi::AVPixelFormat;
+    const YUV444_FORMAT: ffi::AVPixelFormat;
+
+    /// Sample as a fraction of full scale (0-1).
+    fn to_unit(self) -> f32;
+    /// Sample from a fraction of full scale, clamped to 0-1.
+    fn from_unit(v: f32) -> Self;
+    /// Sample from an 8-bit code value, shifted up to this depth the way
+    /// limited-range video stores it (235 -> 235 << 8 for 16-bit).
+    fn from_code8(v: f32) -> Self;
+
+    /// Cubic resize of interleaved RGB data.
+    fn resize_rgb(src: &[Self], sw: u32, sh: u32, dw: u32, dh: u32) -> Vec<Self> {
+        Self::resize_rgb_with(src, sw, sh, dw, dh, FilterType::CatmullRom)
+    }
+    /// Resize of interleaved RGB data with the given filter.
+    fn resize_rgb_with(src: &[Self], sw: u32, sh: u32, dw: u32, dh: u32, filter: FilterType) -> Vec<Self>;
+    /// Cubic resize of a single plane.
+    fn resize_luma(src: &[Self], sw: u32, sh: u32, dw: u32, dh: u32) -> Vec<Self>;
+
+    fn yuv_format(subsampling: ChromaSubsampling) -> ffi::AVPixelFormat {
+        match subsampling {
+            ChromaSubsampling::Yuv420 => Self::YUV420_FORMAT,
+            ChromaSubsampling::Yuv422 => Self::YUV422_FORMAT,
+            ChromaSubsampling::Yuv444 => Self::YUV444_FORMAT,
+        }
+    }
+}
+
+macro_rules! impl_sample {
+    ($t:ty, $rgb:expr, [$yuv420:expr, $yuv422:expr, $yuv444:expr], $shift:expr) => {
+        impl Sample for $t {
+            const RGB_FORMAT: ffi::AVPixelFormat = $rgb;
+            const YUV420_FORMAT: ffi::AVPixelFormat = $yuv420;
+            const YUV422_FORMAT: ffi::AVPixelFormat = $yuv422;
+            const YUV444_FORMAT: ffi::AVPixelFormat = $yuv444;
+
+            fn to_unit(self) -> f32 {
+                self as f32 / <$t>::MAX as f32
+            }
+
+            fn from_unit(v: f32) -> Self {
+                (v.clamp(0.0, 1.0) * <$t>::MAX as f32).round() as $t
+            }
+
+            fn from_code8(v: f32) -> Self {
+                (v * (1u32 << $shift) as f32).round().clamp(0.0, <$t>::MAX as f32) as $t
+            }
+
+            fn resize_rgb_with(src: &[Self], sw: u32, sh: u32, dw: u32, dh: u32, filter: FilterType) -> Vec<Self> {
+                if let Some(img) = ImageBuffer::<Rgb<$t>, Vec<$t>>::from_raw(sw, sh, src.to_vec()) {
+                    return imageops::resize(&img, dw, dh, filter).into_raw();
+                }
+                vec![0; (dw * dh * 3) as usize]
+            }
+
+            fn resize_luma(src: &[Self], sw: u32, sh: u32, dw: u32, dh: u32) -> Vec<Self> {
+                if let Some(img) = ImageBuffer::<Luma<$t>, Vec<$t>>::from_raw(sw, sh, src.to_vec()) {
+                    return imageops::resize(&img, dw, dh, FilterType::CatmullRom).into_raw();
+                }
+                vec![0; (dw * dh) as usize]
+            }
+        }
+    };
+}
+
+impl_sample!(u8, ffi::AV_PIX_FMT_RGB24,
+    [ffi::AV_PIX_FMT_YUV420P, ffi::AV_PIX_FMT_YUV422P, ffi::AV_PIX_FMT_YUV444P], 0);
+impl_sample!(u16, ffi::AV_PIX_FMT_RGB48LE,
+    [ffi::AV_PIX_FMT_YUV420P16LE, ffi::AV_PIX_FMT_YUV422P16LE, ffi::AV_PIX_FMT_YUV444P16LE], 8);
+
+/// Sample depth of the pipeline between decoder and encoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BitDepth {
+    /// 16-bit when the source has more than 8 bits per component, 8-bit otherwise.
+    #[default]
+    Auto,
+    Eight,
+    Sixteen,
+}
+
+/// Chroma resolution of planar YUV frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChromaSubsampling {
+    #[default]
+    Yuv420,
+    Yuv422,
+    Yuv444,
+}
+
+impl ChromaSubsampling {
+    /// Size of each chroma plane for a `w`x`h` frame, rounded up for odd dimensions.
+    pub fn chroma_size(&self, w: i32, h: i32) -> (i32, i32) {
+        match self {
+            ChromaSubsampling::Yuv420 => ((w + 1) / 2, (h + 1) / 2),
+            ChromaSubsampling::Yuv422 => ((w + 1) / 2, h),
+            ChromaSubsampling::Yuv444 => (w, h),
+        }
+    }
+
+    /// Width and height of the Y, U and V planes of a `w`x`h` frame.
+    pub fn plane_sizes(&self, w: i32, h: i32) -> [(i32, i32); 3] {
+        let chroma = self.chroma_size(w, h);
+        [(w, h), chroma, chroma]
+    }
+}
+
 #[derive(Debug, Clone)]
-pub struct RawFrame {
-    pub data: Vec<u8>,
+pub struct RawFrame<T = u8> {
+    pub data: Vec<T>, // interleaved RGB
     pub width: i32,
     pub height: i32,
-    pub pts: i64,
+    pub pts: i64,      // in `time_base` units, AV_NOPTS_VALUE if unknown
+    pub duration: i64, // in `time_base` units, 0 if unknown
+    pub time_base: ffi::AVRational,
+    pub scene_cut: bool, // first frame of a new scene, set by the sequencer
 }
 
 #[derive(Debug, Clone)]
-pub struct UpscaledFrame {
-    pub data: Vec<u8>,
+pub struct UpscaledFrame<T = u8> {
+    pub data: Vec<T>, // planar YUV, chroma subsampled as the encoder expects
     pub width: i32,
     pub height: i32,
-    pub pts: i64,
+    pub pts: i64,      // in `time_base` units, AV_NOPTS_VALUE if unknown
+    pub duration: i64, // in `time_base` units, 0 if unknown
+    pub time_base: ffi::AVRational,
+    pub scene_cut: bool, // encoded as a keyframe
 }
 
 #[derive(Debug, Clone)]
@@ -27,14 +151,14 @@ pub struct PacketData {
     pub pos: i64,
 }
 
-pub enum DecoderMsg {
-    Video(RawFrame),
-    Audio(PacketData),
+pub enum DecoderMsg<T = u8> {
+    Video(RawFrame<T>),
+    Packet(PacketData), // any passed-through stream, routed by `stream_index`
     EOF,
 }
 
-pub enum EncoderMsg {
-    Video(UpscaledFrame),
-    Audio(PacketData),
+pub enum EncoderMsg<T = u8> {
+    Video(UpscaledFrame<T>),
+    Packet(PacketData), // any passed-through stream, routed by `stream_index`
     EOF,
 }
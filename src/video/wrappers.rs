@@ -1,112 +1,423 @@
 // src/video/wrappers.rs
+//
+// Owning wrappers around FFmpeg objects. Each one is non-null from construction
+// on and frees its object on drop. Those backed by a public FFmpeg struct deref
+// to it, so plain fields (pts, width, time_base, ...) are read and set without
+// `unsafe`; everything that calls into FFmpeg is a method that checks the result.
 
 use rsmpeg::ffi;
+use std::ffi::{CStr, CString};
+use std::ops::{Deref, DerefMut};
+use std::os::raw::c_void;
 use std::ptr;
+use anyhow::{Context, Result, anyhow};
+use crate::error::XStreamError;
+use crate::video::averror::{check, received};
+use crate::video::types::PacketData;
+
+// Safe field access for wrappers whose pointer is valid for their whole life
+macro_rules! impl_deref {
+    ($wrapper:ty, $target:ty) => {
+        impl Deref for $wrapper {
+            type Target = $target;
+            fn deref(&self) -> &$target {
+                unsafe { &*self.ptr }
+            }
+        }
+
+        impl DerefMut for $wrapper {
+            fn deref_mut(&mut self) -> &mut $target {
+                unsafe { &mut *self.ptr }
+            }
+        }
+    };
+}
+
+/// Decoder for `id`, if this FFmpeg build has one.
+pub fn find_decoder(id: ffi::AVCodecID) -> Option<&'static ffi::AVCodec> {
+    unsafe { ffi::avcodec_find_decoder(id).as_ref() }
+}
+
+/// Short name of a codec implementation, e.g. "libx264".
+pub fn codec_name(codec: &ffi::AVCodec) -> String {
+    unsafe { CStr::from_ptr(codec.name).to_string_lossy().into_owned() }
+}
+
+/// Encoder called `name` (e.g. "libx264"), or any encoder for `id` if that one is missing.
+pub fn find_encoder(name: &str, id: ffi::AVCodecID) -> Option<&'static ffi::AVCodec> {
+    let name_c = CString::new(name).ok()?;
+    unsafe {
+        ffi::avcodec_find_encoder_by_name(name_c.as_ptr()).as_ref()
+            .or_else(|| ffi::avcodec_find_encoder(id).as_ref())
+    }
+}
+
+/// Codec parameters of a stream that belongs to an open format context.
+pub fn codecpar(stream: &ffi::AVStream) -> &ffi::AVCodecParameters {
+    unsafe { &*stream.codecpar }
+}
+
+/// Mutable codec parameters of an output stream, before the header is written.
+pub fn codecpar_mut(stream: &mut ffi::AVStream) -> &mut ffi::AVCodecParameters {
+    unsafe { &mut *stream.codecpar }
+}
+
+/// `ts` converted from time base `from` to `to`, rounded to nearest.
+pub fn rescale_q(ts: i64, from: ffi::AVRational, to: ffi::AVRational) -> i64 {
+    unsafe { ffi::av_rescale_q(ts, from, to) }
+}
+
+/// Bits per sample of the first component of a pixel format, None if it is unknown.
+pub fn pix_fmt_depth(format: ffi::AVPixelFormat) -> Option<i32> {
+    unsafe { ffi::av_pix_fmt_desc_get(format).as_ref().map(|desc| desc.comp[0].depth) }
+}
+
+/// Gives an output stream the codec parameters of an input stream, for stream copy.
+pub fn copy_codecpar(out: &mut ffi::AVStream, input: &ffi::AVStream) -> Result<()> {
+    check(unsafe { ffi::avcodec_parameters_copy(out.codecpar, input.codecpar) }, "avcodec_parameters_copy")?;
+    Ok(())
+}
 
 // --- AVPacket Wrapper ---
 pub struct SafePacket {
-    pub ptr: *mut ffi::AVPacket,
+    ptr: *mut ffi::AVPacket,
 }
 
 impl SafePacket {
-    pub fn new() -> Self {
+    pub fn new() -> Result<Self> {
+        let ptr = unsafe { ffi::av_packet_alloc() };
+        if ptr.is_null() {
+            return Err(anyhow!("Could not allocate a packet"));
+        }
+        Ok(Self { ptr })
+    }
+
+    /// A packet holding a copy of `data`'s payload, timing and stream index.
+    pub fn from_data(data: &PacketData) -> Result<Self> {
+        let mut pkt = Self::new()?;
         unsafe {
-            let ptr = ffi::av_packet_alloc();
-            Self { ptr }
+            check(ffi::av_new_packet(pkt.ptr, data.data.len() as i32), "av_new_packet")?;
+            ptr::copy_nonoverlapping(data.data.as_ptr(), (*pkt.ptr).data, data.data.len());
         }
+        pkt.pts = data.pts;
+        pkt.dts = data.dts;
+        pkt.duration = data.duration;
+        pkt.flags = data.flags;
+        pkt.stream_index = data.stream_index;
+        pkt.pos = data.pos;
+        Ok(pkt)
+    }
+
+    /// Copies payload and timing out, to hand the packet to another thread.
+    pub fn to_data(&self) -> PacketData {
+        let data = if self.data.is_null() || self.size <= 0 {
+            Vec::new()
+        } else {
+            unsafe { std::slice::from_raw_parts(self.data, self.size as usize).to_vec() }
+        };
+        PacketData {
+            data,
+            pts: self.pts,
+            dts: self.dts,
+            stream_index: self.stream_index,
+            flags: self.flags,
+            duration: self.duration,
+            pos: self.pos,
+        }
+    }
+
+    pub fn rescale_ts(&mut self, from: ffi::AVRational, to: ffi::AVRational) {
+        unsafe { ffi::av_packet_rescale_ts(self.ptr, from, to) }
+    }
+
+    pub fn unref(&mut self) {
+        unsafe { ffi::av_packet_unref(self.ptr) }
+    }
+
+    pub fn as_ptr(&self) -> *const ffi::AVPacket {
+        self.ptr
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut ffi::AVPacket {
+        self.ptr
     }
 }
 
+impl_deref!(SafePacket, ffi::AVPacket);
+
 impl Drop for SafePacket {
     fn drop(&mut self) {
         unsafe {
-            if !self.ptr.is_null() {
-                ffi::av_packet_free(&mut self.ptr);
-            }
+            ffi::av_packet_free(&mut self.ptr);
         }
     }
 }
 
 // --- AVFrame Wrapper ---
 pub struct SafeFrame {
-    pub ptr: *mut ffi::AVFrame,
+    ptr: *mut ffi::AVFrame,
 }
 
 impl SafeFrame {
-    pub fn new() -> Self {
-        unsafe {
-            let ptr = ffi::av_frame_alloc();
-            Self { ptr }
+    pub fn new() -> Result<Self> {
+        let ptr = unsafe { ffi::av_frame_alloc() };
+        if ptr.is_null() {
+            return Err(anyhow!("Could not allocate a frame"));
+        }
+        Ok(Self { ptr })
+    }
+
+    /// A `width`x`height` video frame in `format` with its own buffers.
+    pub fn video(width: i32, height: i32, format: ffi::AVPixelFormat) -> Result<Self> {
+        let mut frame = Self::new()?;
+        frame.width = width;
+        frame.height = height;
+        frame.format = format;
+        frame.alloc_buffers(32)?;
+        Ok(frame)
+    }
+
+    /// Allocates buffers for the size/format/layout already set on the frame.
+    pub fn alloc_buffers(&mut self, align: i32) -> Result<()> {
+        check(unsafe { ffi::av_frame_get_buffer(self.ptr, align) }, "av_frame_get_buffer")?;
+        Ok(())
+    }
+
+    /// Gives the frame buffers nobody else references, copying if an encoder still holds them.
+    pub fn make_writable(&mut self) -> Result<()> {
+        check(unsafe { ffi::av_frame_make_writable(self.ptr) }, "av_frame_make_writable")?;
+        Ok(())
+    }
+
+    pub fn unref(&mut self) {
+        unsafe { ffi::av_frame_unref(self.ptr) }
+    }
+
+    /// Copies packed planes (each `planes[i]` samples wide and high, rows back to back)
+    /// into the frame's padded lines.
+    pub fn write_planes<T: Copy>(&mut self, data: &[T], planes: &[(i32, i32)]) -> Result<()> {
+        let needed: usize = planes.iter().map(|&(w, h)| (w * h) as usize).sum();
+        if data.len() < needed {
+            return Err(anyhow!("Frame data has {} samples, expected {}", data.len(), needed));
         }
+        let sample_size = std::mem::size_of::<T>();
+        let mut offset = 0;
+        for (p, &(pw, ph)) in planes.iter().enumerate() {
+            let (pw, ph) = (pw as usize, ph as usize);
+            let linesize = self.linesize[p] as usize;
+            let dst = self.data[p];
+            if dst.is_null() || linesize < pw * sample_size {
+                return Err(anyhow!("Frame plane {} cannot hold lines of {} samples", p, pw));
+            }
+            for (row, src) in data[offset..offset + pw * ph].chunks_exact(pw).enumerate() {
+                unsafe {
+                    ptr::copy_nonoverlapping(src.as_ptr() as *const u8, dst.add(row * linesize), pw * sample_size);
+                }
+            }
+            offset += pw * ph;
+        }
+        Ok(())
+    }
+
+    pub fn as_ptr(&self) -> *const ffi::AVFrame {
+        self.ptr
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut ffi::AVFrame {
+        self.ptr
     }
 }
 
+impl_deref!(SafeFrame, ffi::AVFrame);
+
 impl Drop for SafeFrame {
     fn drop(&mut self) {
         unsafe {
-            if !self.ptr.is_null() {
-                ffi::av_frame_free(&mut self.ptr);
-            }
+            ffi::av_frame_free(&mut self.ptr);
         }
     }
 }
 
 // --- AVFormatContext (Input) Wrapper ---
 pub struct SafeFormatContextInput {
-    pub ptr: *mut ffi::AVFormatContext,
+    ptr: *mut ffi::AVFormatContext,
 }
 
 impl SafeFormatContextInput {
-    pub fn new() -> Self {
-        Self { ptr: ptr::null_mut() }
+    /// Opens `path` and reads enough of it to know its streams.
+    pub fn open(path: &str) -> Result<Self> {
+        let path_c = CString::new(path)?;
+        let mut ptr = ptr::null_mut();
+        check(unsafe { ffi::avformat_open_input(&mut ptr, path_c.as_ptr(), ptr::null_mut(), ptr::null_mut()) }, "avformat_open_input")
+            .with_context(|| format!("Could not open input {}", path))?;
+        let ctx = Self { ptr };
+        check(unsafe { ffi::avformat_find_stream_info(ctx.ptr, ptr::null_mut()) }, "avformat_find_stream_info")?;
+        Ok(ctx)
+    }
+
+    pub fn streams(&self) -> impl Iterator<Item = &ffi::AVStream> + '_ {
+        (0..self.nb_streams as usize).map(move |i| unsafe { &**self.streams.add(i) })
+    }
+
+    pub fn stream(&self, index: usize) -> Option<&ffi::AVStream> {
+        self.streams().nth(index)
+    }
+
+    /// Reads the next packet of any stream; false at end of file.
+    pub fn read_packet(&mut self, pkt: &mut SafePacket) -> Result<bool> {
+        let ret = unsafe { ffi::av_read_frame(self.ptr, pkt.as_mut_ptr()) };
+        if ret == ffi::AVERROR_EOF {
+            return Ok(false);
+        }
+        check(ret, "av_read_frame")?;
+        Ok(true)
+    }
+
+    /// Index of the stream FFmpeg picks as the main one of `media_type`.
+    pub fn best_stream(&self, media_type: ffi::AVMediaType) -> Option<usize> {
+        let idx = unsafe { ffi::av_find_best_stream(self.ptr, media_type, -1, -1, ptr::null_mut(), 0) };
+        if idx < 0 { None } else { Some(idx as usize) }
+    }
+
+    /// Sample aspect ratio of a stream, from the container or the codec, 0/1 if unknown.
+    pub fn sample_aspect_ratio(&self, index: usize) -> ffi::AVRational {
+        match self.stream(index) {
+            Some(stream) => unsafe {
+                ffi::av_guess_sample_aspect_ratio(self.ptr, stream as *const _ as *mut _, ptr::null_mut())
+            },
+            None => ffi::AVRational { num: 0, den: 1 },
+        }
+    }
+
+    pub fn as_ptr(&self) -> *const ffi::AVFormatContext {
+        self.ptr
     }
 }
 
+impl_deref!(SafeFormatContextInput, ffi::AVFormatContext);
+
 impl Drop for SafeFormatContextInput {
     fn drop(&mut self) {
         unsafe {
-            if !self.ptr.is_null() {
-                ffi::avformat_close_input(&mut self.ptr);
-            }
+            ffi::avformat_close_input(&mut self.ptr);
         }
     }
 }
 
 // --- AVFormatContext (Output) Wrapper ---
 pub struct SafeFormatContextOutput {
-    pub ptr: *mut ffi::AVFormatContext,
+    ptr: *mut ffi::AVFormatContext,
 }
 
 impl SafeFormatContextOutput {
-    pub fn new() -> Self {
-        Self { ptr: ptr::null_mut() }
+    /// Output context for `path`; the container comes from `format` or the file extension.
+    /// Nothing is opened or written yet.
+    pub fn create(path: &str, format: Option<&str>) -> Result<Self> {
+        let path_c = CString::new(path)?;
+        let format_c = format.map(CString::new).transpose()?;
+        let mut ptr = ptr::null_mut();
+        let ret = unsafe {
+            ffi::avformat_alloc_output_context2(
+                &mut ptr, ptr::null_mut(),
+                format_c.as_ref().map_or(ptr::null(), |c| c.as_ptr()), path_c.as_ptr()
+            )
+        };
+        check(ret, "avformat_alloc_output_context2")
+            .with_context(|| format!("Could not allocate output context (container: {:?})", format))
+            .map_err(XStreamError::Mux)?;
+        Ok(Self { ptr })
+    }
+
+    /// Adds a stream and returns its index.
+    pub fn add_stream(&mut self) -> Result<usize> {
+        let stream = unsafe { ffi::avformat_new_stream(self.ptr, ptr::null()) };
+        if stream.is_null() {
+            return Err(XStreamError::Mux(anyhow!("Could not add a stream to the output")).into());
+        }
+        Ok(self.nb_streams as usize - 1)
+    }
+
+    pub fn stream(&self, index: usize) -> Option<&ffi::AVStream> {
+        (index < self.nb_streams as usize).then(|| unsafe { &**self.streams.add(index) })
+    }
+
+    pub fn stream_mut(&mut self, index: usize) -> Option<&mut ffi::AVStream> {
+        (index < self.nb_streams as usize).then(|| unsafe { &mut **self.streams.add(index) })
+    }
+
+    /// Short name of the container, e.g. "mp4".
+    pub fn format_name(&self) -> String {
+        unsafe { CStr::from_ptr((*self.oformat).name).to_string_lossy().into_owned() }
+    }
+
+    /// Whether encoders must put codec headers in extradata rather than in the stream.
+    pub fn needs_global_header(&self) -> bool {
+        unsafe { ((*self.oformat).flags & ffi::AVFMT_GLOBALHEADER as i32) != 0 }
+    }
+
+    /// Some(true/false) if the container accepts/rejects `codec`, None if it keeps no list.
+    pub fn accepts_codec(&self, codec: ffi::AVCodecID) -> Option<bool> {
+        match unsafe { ffi::avformat_query_codec(self.oformat, codec, ffi::FF_COMPLIANCE_NORMAL) } {
+            1 => Some(true),
+            0 => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Opens the output file (unless the container writes none) and writes the header.
+    pub fn write_header(&mut self, path: &str) -> Result<()> {
+        let path_c = CString::new(path)?;
+        unsafe {
+            if ((*self.oformat).flags & ffi::AVFMT_NOFILE as i32) == 0 {
+                let ret = ffi::avio_open(&mut (*self.ptr).pb, path_c.as_ptr(), ffi::AVIO_FLAG_WRITE as i32);
+                if ret < 0 {
+                    return Err(XStreamError::io(ret, path).into());
+                }
+            }
+            check(ffi::avformat_write_header(self.ptr, ptr::null_mut()), "avformat_write_header")
+                .with_context(|| format!("Could not write the output header for {}", path))
+                .map_err(XStreamError::Mux)?;
+        }
+        Ok(())
+    }
+
+    /// Interleaves and writes a packet; the packet is left blank.
+    pub fn write_packet(&mut self, pkt: &mut SafePacket) -> Result<()> {
+        let stream_index = pkt.stream_index;
+        check(unsafe { ffi::av_interleaved_write_frame(self.ptr, pkt.as_mut_ptr()) }, "av_interleaved_write_frame")
+            .with_context(|| format!("Could not write a packet of output stream #{}", stream_index))
+            .map_err(XStreamError::Mux)?;
+        Ok(())
+    }
+
+    /// Bytes written to the output file so far, 0 before it is opened.
+    pub fn bytes_written(&self) -> u64 {
+        if self.pb.is_null() {
+            return 0;
+        }
+        // avio_tell() is a macro for this
+        let pos = unsafe { ffi::avio_seek(self.pb, 0, libc::SEEK_CUR) };
+        pos.max(0) as u64
+    }
+
+    pub fn write_trailer(&mut self) -> Result<()> {
+        check(unsafe { ffi::av_write_trailer(self.ptr) }, "av_write_trailer")
+            .context("Could not write the output trailer")
+            .map_err(XStreamError::Mux)?;
+        Ok(())
     }
 }
 
+impl_deref!(SafeFormatContextOutput, ffi::AVFormatContext);
+
 impl Drop for SafeFormatContextOutput {
     fn drop(&mut self) {
         unsafe {
-            if !self.ptr.is_null() {
-                // For output, we usually do avformat_free_context if we allocated it,
-                // BUT if we opened file (AVIO), we also need to close it.
-                // The main flow usually closes pb and frees context.
-                
-                // Note: If avformat_write_header wasn't called or failed, we just free.
-                // If we aren't sure, we should follow manual cleanup or be very careful.
-                // Standard: avformat_free_context handles the structure.
-                
-                // If we opened the IO context (pb), we must close it separately usually
-                // via avio_closep. 
-                
-                if !(*self.ptr).pb.is_null() {
-                     // Check if we should close it (standard flags check)
-                     if ((*(*self.ptr).oformat).flags as i32 & ffi::AVFMT_NOFILE as i32) == 0 {
-                          ffi::avio_closep(&mut (*self.ptr).pb);
-                     }
-                }
-                
-                ffi::avformat_free_context(self.ptr);
+            // The file was opened by write_header unless the container writes none
+            if !(*self.ptr).pb.is_null() && ((*(*self.ptr).oformat).flags & ffi::AVFMT_NOFILE as i32) == 0 {
+                ffi::avio_closep(&mut (*self.ptr).pb);
             }
+            ffi::avformat_free_context(self.ptr);
         }
     }
 }
@@ -114,31 +425,86 @@ impl Drop for SafeFormatContextOutput {
 
 // --- AVCodecContext Wrapper ---
 pub struct SafeCodecContext {
-    pub ptr: *mut ffi::AVCodecContext,
+    ptr: *mut ffi::AVCodecContext,
 }
 
 impl SafeCodecContext {
-    pub fn new(codec: *const ffi::AVCodec) -> Self {
-        unsafe {
-            let ptr = ffi::avcodec_alloc_context3(codec);
-            Self { ptr }
+    /// Unopened context for `codec`; set its fields, then call `open`.
+    pub fn new(codec: &'static ffi::AVCodec) -> Result<Self> {
+        let ptr = unsafe { ffi::avcodec_alloc_context3(codec) };
+        if ptr.is_null() {
+            return Err(anyhow!("Could not allocate a codec context"));
+        }
+        Ok(Self { ptr })
+    }
+
+    /// Opened decoder for a stream's codec parameters.
+    pub fn decoder(par: &ffi::AVCodecParameters, pkt_timebase: ffi::AVRational) -> Result<Self> {
+        let codec = find_decoder(par.codec_id).ok_or_else(|| anyhow!("No decoder for codec id {}", par.codec_id))?;
+        let mut ctx = Self::new(codec)?;
+        check(unsafe { ffi::avcodec_parameters_to_context(ctx.ptr, par) }, "avcodec_parameters_to_context")?;
+        ctx.pkt_timebase = pkt_timebase;
+        ctx.open(&mut SafeDictionary::new())?;
+        Ok(ctx)
+    }
+
+    /// Opens the codec. Options it consumes are removed from `options`.
+    pub fn open(&mut self, options: &mut SafeDictionary) -> Result<()> {
+        check(unsafe { ffi::avcodec_open2(self.ptr, self.codec, &mut options.ptr) }, "avcodec_open2")?;
+        Ok(())
+    }
+
+    pub fn codec(&self) -> &'static ffi::AVCodec {
+        unsafe { &*self.codec }
+    }
+
+    /// Feeds a packet to a decoder (None = drain). Ok(false) if it was rejected as
+    /// corrupt; the decoder then resyncs on a later packet.
+    pub fn send_packet(&mut self, pkt: Option<&SafePacket>) -> Result<bool> {
+        let ret = unsafe { ffi::avcodec_send_packet(self.ptr, pkt.map_or(ptr::null(), |p| p.as_ptr())) };
+        if ret == ffi::AVERROR_INVALIDDATA {
+            return Ok(false);
         }
+        check(ret, "avcodec_send_packet")?;
+        Ok(true)
+    }
+
+    /// Next decoded frame; false once the decoder needs more input or is drained.
+    pub fn receive_frame(&mut self, frame: &mut SafeFrame) -> Result<bool> {
+        received(unsafe { ffi::avcodec_receive_frame(self.ptr, frame.as_mut_ptr()) }, "avcodec_receive_frame")
+    }
+
+    /// Feeds a frame to an encoder (None = drain).
+    pub fn send_frame(&mut self, frame: Option<&SafeFrame>) -> Result<()> {
+        check(unsafe { ffi::avcodec_send_frame(self.ptr, frame.map_or(ptr::null(), |f| f.as_ptr())) }, "avcodec_send_frame")?;
+        Ok(())
+    }
+
+    /// Next encoded packet; false once the encoder needs more input or is drained.
+    pub fn receive_packet(&mut self, pkt: &mut SafePacket) -> Result<bool> {
+        received(unsafe { ffi::avcodec_receive_packet(self.ptr, pkt.as_mut_ptr()) }, "avcodec_receive_packet")
+    }
+
+    /// Describes an opened encoder's output on `stream`, extradata included.
+    pub fn copy_params_to(&self, stream: &mut ffi::AVStream) -> Result<()> {
+        check(unsafe { ffi::avcodec_parameters_from_context(stream.codecpar, self.ptr) }, "avcodec_parameters_from_context")?;
+        Ok(())
     }
 }
 
+impl_deref!(SafeCodecContext, ffi::AVCodecContext);
+
 impl Drop for SafeCodecContext {
     fn drop(&mut self) {
         unsafe {
-            if !self.ptr.is_null() {
-                ffi::avcodec_free_context(&mut self.ptr);
-            }
+            ffi::avcodec_free_context(&mut self.ptr);
         }
     }
 }
 
 // --- SwsContext Wrapper ---
 pub struct SafeSwsContext {
-    pub ptr: *mut ffi::SwsContext,
+    ptr: *mut ffi::SwsContext,
 }
 
 impl SafeSwsContext {
@@ -146,24 +512,227 @@ impl SafeSwsContext {
         src_w: i32, src_h: i32, src_fmt: i32,
         dst_w: i32, dst_h: i32, dst_fmt: i32,
         flags: i32
-    ) -> Self {
-        unsafe {
-            let ptr = ffi::sws_getContext(
+    ) -> Result<Self> {
+        let ptr = unsafe {
+            ffi::sws_getContext(
                 src_w, src_h, src_fmt,
                 dst_w, dst_h, dst_fmt,
                 flags, ptr::null_mut(), ptr::null_mut(), ptr::null()
+            )
+        };
+        if ptr.is_null() {
+            return Err(anyhow!("Could not create a {}x{} -> {}x{} scaler (formats {} -> {})", src_w, src_h, dst_w, dst_h, src_fmt, dst_fmt));
+        }
+        Ok(Self { ptr })
+    }
+
+    /// YUV matrix (`SWS_CS_*`) and range of each side; the RGB side's matrix is ignored.
+    pub fn set_colorspace(&mut self, src_space: i32, src_full_range: bool, dst_space: i32, dst_full_range: bool) -> Result<()> {
+        unsafe {
+            let ret = ffi::sws_setColorspaceDetails(
+                self.ptr, ffi::sws_getCoefficients(src_space), src_full_range as i32,
+                ffi::sws_getCoefficients(dst_space), dst_full_range as i32, 0, 1 << 16, 1 << 16
             );
-            Self { ptr }
+            check(ret, "sws_setColorspaceDetails")?;
+        }
+        Ok(())
+    }
+
+    /// Converts a whole frame into another of the scaler's output size and format.
+    pub fn scale_frame(&mut self, src: &SafeFrame, dst: &mut SafeFrame) -> Result<()> {
+        unsafe {
+            check(ffi::sws_scale(
+                self.ptr, src.data.as_ptr() as *const *const u8, src.linesize.as_ptr(),
+                0, src.height, dst.data.as_ptr(), dst.linesize.as_ptr()
+            ), "sws_scale")?;
+        }
+        Ok(())
+    }
+
+    /// Converts a frame to packed 3-channel samples (RGB24/RGB48) of the same size.
+    pub fn frame_to_packed<T: Copy>(&mut self, src: &SafeFrame, dst: &mut [T]) -> Result<()> {
+        let (w, h) = (src.width, src.height);
+        if dst.len() < (w * h * 3) as usize {
+            return Err(anyhow!("Buffer of {} samples is too small for a {}x{} RGB frame", dst.len(), w, h));
+        }
+        let dst_ptr = [dst.as_mut_ptr() as *mut u8, ptr::null_mut(), ptr::null_mut(), ptr::null_mut()];
+        let dst_linesize = [w * 3 * std::mem::size_of::<T>() as i32, 0, 0, 0];
+        unsafe {
+            check(ffi::sws_scale(
+                self.ptr, src.data.as_ptr() as *const *const u8, src.linesize.as_ptr(),
+                0, h, dst_ptr.as_ptr(), dst_linesize.as_ptr()
+            ), "sws_scale")?;
         }
+        Ok(())
+    }
+
+    /// Converts a packed 3-channel `width`x`height` image into planes packed back to
+    /// back in `dst`, each `planes[i]` samples wide and high.
+    pub fn packed_to_planes<T: Copy>(&mut self, src: &[T], width: i32, height: i32, dst: &mut [T], planes: &[(i32, i32)]) -> Result<()> {
+        if src.len() < (width * height * 3) as usize {
+            return Err(anyhow!("RGB frame has {} samples, expected {}", src.len(), width * height * 3));
+        }
+        let needed: usize = planes.iter().map(|&(w, h)| (w * h) as usize).sum();
+        if planes.len() > 4 || dst.len() < needed {
+            return Err(anyhow!("Plane buffer has {} samples, expected {}", dst.len(), needed));
+        }
+        let sample_size = std::mem::size_of::<T>() as i32;
+        let mut dst_ptr: [*mut u8; 4] = [ptr::null_mut(); 4];
+        let mut dst_linesize = [0i32; 4];
+        let mut offset = 0;
+        for (i, &(w, h)) in planes.iter().enumerate() {
+            dst_ptr[i] = dst[offset..].as_mut_ptr() as *mut u8;
+            dst_linesize[i] = w * sample_size;
+            offset += (w * h) as usize;
+        }
+        let src_ptr = [src.as_ptr() as *const u8, ptr::null(), ptr::null(), ptr::null()];
+        let src_linesize = [width * 3 * sample_size, 0, 0, 0];
+        unsafe {
+            check(ffi::sws_scale(
+                self.ptr, src_ptr.as_ptr(), src_linesize.as_ptr(),
+                0, height, dst_ptr.as_ptr(), dst_linesize.as_ptr()
+            ), "sws_scale")?;
+        }
+        Ok(())
     }
 }
 
 impl Drop for SafeSwsContext {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::sws_freeContext(self.ptr);
+        }
+    }
+}
+
+// --- AVDictionary Wrapper ---
+pub struct SafeDictionary {
+    ptr: *mut ffi::AVDictionary,
+}
+
+impl SafeDictionary {
+    pub fn new() -> Self {
+        Self { ptr: ptr::null_mut() }
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) {
+        let (Ok(k), Ok(v)) = (CString::new(key), CString::new(value)) else { return };
+        unsafe {
+            ffi::av_dict_set(&mut self.ptr, k.as_ptr(), v.as_ptr(), 0);
+        }
+    }
+
+    /// Keys still present in the dictionary (after `avcodec_open2` these are the options nobody consumed).
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys = Vec::new();
+        unsafe {
+            let empty = CString::new("").unwrap();
+            let mut entry: *mut ffi::AVDictionaryEntry = ptr::null_mut();
+            loop {
+                entry = ffi::av_dict_get(self.ptr, empty.as_ptr(), entry, ffi::AV_DICT_IGNORE_SUFFIX as i32);
+                if entry.is_null() {
+                    break;
+                }
+                keys.push(CStr::from_ptr((*entry).key).to_string_lossy().into_owned());
+            }
+        }
+        keys
+    }
+}
+
+impl Default for SafeDictionary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for SafeDictionary {
     fn drop(&mut self) {
         unsafe {
             if !self.ptr.is_null() {
-                ffi::sws_freeContext(self.ptr);
+                ffi::av_dict_free(&mut self.ptr);
             }
         }
     }
 }
+
+// --- SwrContext Wrapper ---
+pub struct SafeSwrContext {
+    ptr: *mut ffi::SwrContext,
+}
+
+impl SafeSwrContext {
+    /// Resampler between two layout/format/rate triples.
+    pub fn new(
+        out_layout: &ffi::AVChannelLayout, out_fmt: ffi::AVSampleFormat, out_rate: i32,
+        in_layout: &ffi::AVChannelLayout, in_fmt: ffi::AVSampleFormat, in_rate: i32,
+    ) -> Result<Self> {
+        let mut swr = Self { ptr: ptr::null_mut() };
+        unsafe {
+            check(ffi::swr_alloc_set_opts2(
+                &mut swr.ptr, out_layout, out_fmt, out_rate,
+                in_layout, in_fmt, in_rate, 0, ptr::null_mut()
+            ), "swr_alloc_set_opts2")?;
+            check(ffi::swr_init(swr.ptr), "swr_init")?;
+        }
+        Ok(swr)
+    }
+
+    /// Resamples `input` (None = flush the resampler's delay) into `out`, which
+    /// must carry the output layout/format/rate.
+    pub fn convert_frame(&mut self, out: &mut SafeFrame, input: Option<&SafeFrame>) -> Result<()> {
+        let input = input.map_or(ptr::null(), |f| f.as_ptr());
+        check(unsafe { ffi::swr_convert_frame(self.ptr, out.as_mut_ptr(), input) }, "swr_convert_frame")?;
+        Ok(())
+    }
+}
+
+impl Drop for SafeSwrContext {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::swr_free(&mut self.ptr);
+        }
+    }
+}
+
+// --- AVAudioFifo Wrapper ---
+pub struct SafeAudioFifo {
+    ptr: *mut ffi::AVAudioFifo,
+}
+
+impl SafeAudioFifo {
+    pub fn new(sample_fmt: ffi::AVSampleFormat, channels: i32) -> Result<Self> {
+        let ptr = unsafe { ffi::av_audio_fifo_alloc(sample_fmt, channels, 1) };
+        if ptr.is_null() {
+            return Err(anyhow!("Could not allocate the audio FIFO"));
+        }
+        Ok(Self { ptr })
+    }
+
+    /// Samples currently buffered.
+    pub fn size(&self) -> i32 {
+        unsafe { ffi::av_audio_fifo_size(self.ptr) }
+    }
+
+    /// Appends all samples of `frame`, which must match the FIFO's format and channels.
+    pub fn write(&mut self, frame: &SafeFrame) -> Result<()> {
+        let data = frame.extended_data as *const *mut c_void;
+        check(unsafe { ffi::av_audio_fifo_write(self.ptr, data, frame.nb_samples) }, "av_audio_fifo_write")?;
+        Ok(())
+    }
+
+    /// Moves the oldest `frame.nb_samples` samples into `frame`'s buffers.
+    pub fn read(&mut self, frame: &mut SafeFrame) -> Result<()> {
+        let data = frame.extended_data as *const *mut c_void;
+        check(unsafe { ffi::av_audio_fifo_read(self.ptr, data, frame.nb_samples) }, "av_audio_fifo_read")?;
+        Ok(())
+    }
+}
+
+impl Drop for SafeAudioFifo {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::av_audio_fifo_free(self.ptr);
+        }
+    }
+}